@@ -0,0 +1,236 @@
+//! Resolves the compiler the same way cargo does,
+//! honoring the `build.rustc` settings in `.cargo/config.toml`.
+//!
+//! See <https://doc.rust-lang.org/cargo/reference/config.html> for details.
+
+use crate::build::{self, Rustc};
+use crate::toml;
+use crate::VersionDetectionError;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The settings from a single configuration file.
+#[derive(Clone, Debug, Default)]
+struct ConfigFile {
+    rustc: Option<OsString>,
+    rustc_wrapper: Option<OsString>,
+    rustc_workspace_wrapper: Option<OsString>,
+}
+
+/// Resolve the compiler that cargo would use when building in the specified directory.
+///
+/// For each setting, the `RUSTC*` environment variables take precedence,
+/// followed by the `CARGO_BUILD_RUSTC*` environment variables,
+/// followed by the configuration files.
+pub fn resolve_compiler(dir: &Path) -> Result<Rustc, VersionDetectionError> {
+    let files = load_config_files(dir)?;
+    let setting = |env_var: &str, config_env_var: &str, get: fn(&ConfigFile) -> Option<&OsString>| {
+        env::var_os(env_var)
+            .or_else(|| env::var_os(config_env_var))
+            .or_else(|| files.iter().find_map(|file| get(file).cloned()))
+            // an empty string disables a wrapper
+            .filter(|value| !value.is_empty())
+    };
+    let rustc = setting("RUSTC", "CARGO_BUILD_RUSTC", |file| file.rustc.as_ref());
+    let wrapper = setting("RUSTC_WRAPPER", "CARGO_BUILD_RUSTC_WRAPPER", |file| {
+        file.rustc_wrapper.as_ref()
+    });
+    let workspace_wrapper = setting(
+        "RUSTC_WORKSPACE_WRAPPER",
+        "CARGO_BUILD_RUSTC_WORKSPACE_WRAPPER",
+        |file| file.rustc_workspace_wrapper.as_ref(),
+    );
+    Ok(Rustc {
+        rustc: rustc.unwrap_or_else(|| OsString::from("rustc")),
+        wrapper,
+        workspace_wrapper,
+    })
+}
+
+/// The location of cargo's data, from `CARGO_HOME` or defaulting to `~/.cargo`.
+fn cargo_home() -> Option<PathBuf> {
+    if let Some(home) = env::var_os("CARGO_HOME").filter(|home| !home.is_empty()) {
+        return Some(PathBuf::from(home));
+    }
+    Some(build::home_dir()?.join(".cargo"))
+}
+
+/// Load the configuration files which apply to the directory, in order of precedence.
+///
+/// Files in deeper directories take precedence, followed by the file in `CARGO_HOME`.
+fn load_config_files(dir: &Path) -> Result<Vec<ConfigFile>, VersionDetectionError> {
+    let current_dir = env::current_dir().map_err(|e| {
+        VersionDetectionError::with_cause(
+            "Error: failed to determine the current directory".into(),
+            e,
+        )
+    })?;
+    let mut config_dirs = config_dirs(&current_dir, dir);
+    if let Some(home) = cargo_home() {
+        if !config_dirs.contains(&home) {
+            config_dirs.push(home);
+        }
+    }
+    let mut files = Vec::new();
+    for config_dir in config_dirs {
+        // if both files exist, cargo prefers the one without an extension
+        for name in &["config", "config.toml"] {
+            let path = config_dir.join(name);
+            if let Some(file) = read_config_file(&path)? {
+                files.push(file);
+                break;
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// The `.cargo` directories which apply to the directory, from deepest to shallowest.
+///
+/// A relative directory is resolved against the current directory,
+/// so that the parents of the current directory are searched.
+fn config_dirs(current_dir: &Path, dir: &Path) -> Vec<PathBuf> {
+    // normalize to avoid visiting a directory twice, as in `dir/.`
+    let dir = current_dir.join(dir).components().collect::<PathBuf>();
+    dir.ancestors().map(|ancestor| ancestor.join(".cargo")).collect()
+}
+
+fn read_config_file(path: &Path) -> Result<Option<ConfigFile>, VersionDetectionError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        // a directory named `config` is not a configuration file
+        Err(_) if path.is_dir() => return Ok(None),
+        Err(e) => {
+            return Err(VersionDetectionError::with_cause(
+                format!("Error: failed to read `{}`", path.display()),
+                e,
+            ));
+        }
+    };
+    let doc = toml::parse(&text).map_err(|e| {
+        VersionDetectionError::new(format!("Error: failed to parse `{}`: {}", path.display(), e))
+    })?;
+    // relative paths are resolved against the parent of the `.cargo` directory
+    let base = path.parent().and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+    let get = |key: &str| -> Result<Option<OsString>, VersionDetectionError> {
+        match doc.get(&["build", key]) {
+            None => Ok(None),
+            Some(value) => match value.as_str() {
+                Some(value) => Ok(Some(resolve_program_path(base, value))),
+                None => Err(VersionDetectionError::new(format!(
+                    "Error: expected a string for `build.{}` in `{}`",
+                    key,
+                    path.display()
+                ))),
+            },
+        }
+    };
+    Ok(Some(ConfigFile {
+        rustc: get("rustc")?,
+        rustc_wrapper: get("rustc-wrapper")?,
+        rustc_workspace_wrapper: get("rustc-workspace-wrapper")?,
+    }))
+}
+
+/// Resolve a program path from a configuration file.
+///
+/// A value without a path separator is searched for in `PATH`,
+/// while any other relative path is relative to `base`.
+fn resolve_program_path(base: &Path, value: &str) -> OsString {
+    if value.is_empty() || !value.contains(|c| c == '/' || c == '\\') {
+        OsString::from(value)
+    } else {
+        base.join(value).into_os_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{config_dirs, load_config_files, read_config_file, resolve_program_path};
+    use std::ffi::OsString;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn program_path() {
+        let base = Path::new("/project");
+        assert_eq!(resolve_program_path(base, "sccache"), OsString::from("sccache"));
+        assert_eq!(
+            resolve_program_path(base, "tools/rustc"),
+            Path::new("/project/tools/rustc").as_os_str()
+        );
+        assert_eq!(
+            resolve_program_path(base, "/usr/bin/rustc"),
+            OsString::from("/usr/bin/rustc")
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // miri cannot access the filesystem
+    fn config_file() {
+        let dir = std::env::temp_dir()
+            .join(format!("rustversion-detect-config-{}", std::process::id()));
+        let config_dir = dir.join(".cargo");
+        fs::create_dir_all(&config_dir).unwrap();
+        let path = config_dir.join("config.toml");
+        fs::write(
+            &path,
+            "[build]\nrustc = \"bin/rustc\"\nrustc-wrapper = \"sccache\"\n",
+        )
+        .unwrap();
+        let file = read_config_file(&path).unwrap().unwrap();
+        assert_eq!(file.rustc, Some(dir.join("bin/rustc").into_os_string()));
+        assert_eq!(file.rustc_wrapper, Some(OsString::from("sccache")));
+        assert_eq!(file.rustc_workspace_wrapper, None);
+
+        fs::write(&path, "build.rustc = 1\n").unwrap();
+        assert!(read_config_file(&path).is_err());
+        assert!(read_config_file(&config_dir.join("config")).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn search_parents() {
+        let current_dir = Path::new("/workspace/member");
+        let expected = vec![
+            Path::new("/workspace/member/.cargo"),
+            Path::new("/workspace/.cargo"),
+            Path::new("/.cargo"),
+        ];
+        assert_eq!(config_dirs(current_dir, Path::new(".")), expected);
+        assert_eq!(config_dirs(current_dir, Path::new("")), expected);
+        assert_eq!(config_dirs(Path::new("/other"), current_dir), expected);
+        assert_eq!(
+            config_dirs(Path::new("/workspace"), Path::new("member/src"))[0],
+            Path::new("/workspace/member/src/.cargo")
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // miri cannot access the filesystem
+    fn nested_dir() {
+        let dir = std::env::temp_dir()
+            .join(format!("rustversion-detect-nested-{}", std::process::id()));
+        let member = dir.join("crates").join("member");
+        fs::create_dir_all(&member).unwrap();
+        fs::create_dir_all(dir.join(".cargo")).unwrap();
+        fs::write(
+            dir.join(".cargo").join("config.toml"),
+            "[build]\nrustc = \"bin/rustc\"\n",
+        )
+        .unwrap();
+        let files = load_config_files(&member.join(".")).unwrap();
+        assert_eq!(files[0].rustc, Some(dir.join("bin/rustc").into_os_string()));
+        // the same file is not loaded twice
+        let count = files
+            .iter()
+            .filter(|file| file.rustc.as_ref() == Some(&dir.join("bin/rustc").into_os_string()))
+            .count();
+        assert_eq!(count, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
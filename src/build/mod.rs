@@ -1,41 +1,350 @@
-mod rustc;
+pub mod config;
+pub mod rustc;
+pub mod rustup;
 
-use crate::version::RustVersion;
+use crate::detect::{ParseConfidence, ToolContext};
+use crate::report::{Attempt, AttemptOutcome};
+use crate::version::{CompilerKind, RustVersion};
 use crate::VersionDetectionError;
 use std::env;
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
 use std::iter;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Instant;
 
-pub fn determine_version() -> Result<RustVersion, VersionDetectionError> {
-    let rustc = env::var_os("RUSTC").unwrap_or_else(|| OsString::from("rustc"));
-    let rustc_wrapper = env::var_os("RUSTC_WRAPPER").filter(|wrapper| !wrapper.is_empty());
-    let wrapped_rustc = rustc_wrapper.iter().chain(iter::once(&rustc));
+/// Environment variables which can affect the detected version.
+///
+/// The `rustup` proxy reads `RUSTUP_TOOLCHAIN` and `RUSTUP_HOME`.
+/// `RUSTC_BOOTSTRAP` and the flags affect unstable features and the codegen backend,
+/// and the `RUSTDOC` variables affect the detected rustdoc version.
+/// Variables which only affect the [`ToolContext`] are excluded,
+/// to avoid rerunning build scripts when switching between `cargo build` and `cargo clippy`.
+pub const ENV_VARS: &[&str] = &[
+    "RUSTC",
+    "RUSTC_WRAPPER",
+    "RUSTUP_TOOLCHAIN",
+    "RUSTUP_HOME",
+    "RUSTC_BOOTSTRAP",
+    "CARGO_ENCODED_RUSTFLAGS",
+    "RUSTFLAGS",
+    "RUSTDOC",
+    "RUSTDOC_WRAPPER",
+];
 
+/// The home directory of the current user, from `HOME` or `USERPROFILE` on windows.
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// The compiler specified by the `RUSTC` and `RUSTC_WRAPPER` environment variables.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[allow(clippy::struct_field_names)] // the fields mirror the environment variables
+pub struct Rustc {
+    pub rustc: OsString,
+    pub wrapper: Option<OsString>,
+    /// The wrapper used for workspace members, which runs inside `wrapper`.
+    pub workspace_wrapper: Option<OsString>,
+}
+
+impl Rustc {
+    pub fn from_env() -> Rustc {
+        Rustc {
+            rustc: env::var_os("RUSTC").unwrap_or_else(|| OsString::from("rustc")),
+            wrapper: env::var_os("RUSTC_WRAPPER").filter(|wrapper| !wrapper.is_empty()),
+            workspace_wrapper: None,
+        }
+    }
+
+    /// The documentation tool specified by the `RUSTDOC` and `RUSTDOC_WRAPPER` environment variables.
+    ///
+    /// This accepts the same arguments as the compiler for the purpose of version detection.
+    pub fn rustdoc_from_env() -> Rustc {
+        Rustc {
+            rustc: env::var_os("RUSTDOC").unwrap_or_else(|| OsString::from("rustdoc")),
+            wrapper: env::var_os("RUSTDOC_WRAPPER").filter(|wrapper| !wrapper.is_empty()),
+            workspace_wrapper: None,
+        }
+    }
+
+    /// Check if the compiler is run through a wrapper.
+    pub fn has_wrapper(&self) -> bool {
+        self.wrapper.is_some() || self.workspace_wrapper.is_some()
+    }
+
+    /// The programs which are run, from the outermost wrapper to the compiler.
+    pub fn programs(&self) -> impl Iterator<Item = &OsString> {
+        self.wrapper.iter()
+            .chain(self.workspace_wrapper.iter())
+            .chain(iter::once(&self.rustc))
+    }
+
+    /// Create a command which runs the compiler through the wrappers (if any).
+    pub fn command(&self) -> Command {
+        let mut wrapped_rustc = self.programs();
+        let mut command = Command::new(wrapped_rustc.next().unwrap());
+        command.args(wrapped_rustc);
+        command
+    }
+
+    /// Resolve the path to the compiler binary, searching `PATH` if necessary.
+    pub fn resolve_path(&self) -> Option<PathBuf> {
+        let rustc = PathBuf::from(&self.rustc);
+        if rustc.components().count() > 1 {
+            return if rustc.is_file() { Some(rustc) } else { None };
+        }
+        let mut file_name = rustc.into_os_string();
+        if Path::new(&file_name).extension().is_none() {
+            file_name.push(env::consts::EXE_SUFFIX);
+        }
+        let path = env::var_os("PATH")?;
+        env::split_paths(&path)
+            .map(|dir| dir.join(&file_name))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// Run the compiler with `--print <request>`, returning the output.
+    pub fn print(
+        &self,
+        request: &str,
+        target: Option<&str>,
+        rustflags: &[String],
+    ) -> Result<String, VersionDetectionError> {
+        let mut command = self.command();
+        command.arg("--print").arg(request);
+        if let Some(target) = target {
+            command.arg("--target").arg(target);
+        }
+        command.args(rustflags);
+        let description = format!("`{} --print {}`", self.rustc.to_string_lossy(), request);
+        run_command(&mut command, &description)
+    }
+}
+
+/// Run a tool (other than the compiler) with the specified arguments, returning the output.
+pub fn run_tool(program: &OsStr, args: &[&str]) -> Result<String, VersionDetectionError> {
+    let description = format!("`{} {}`", program.to_string_lossy(), args.join(" "));
+    run_command(Command::new(program).args(args), &description)
+}
+
+/// Run the command, returning its output if it succeeds.
+///
+/// The `description` is used in error messages.
+fn run_command(command: &mut Command, description: &str) -> Result<String, VersionDetectionError> {
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(e) => {
+            return Err(VersionDetectionError::with_cause(
+                format!("Error: failed to run {}", description),
+                e,
+            ));
+        }
+    };
+    if !output.status.success() {
+        return Err(VersionDetectionError::new(format!(
+            "Error: {} failed ({}): {}",
+            description,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|_| {
+        VersionDetectionError::new(format!("Error: Invalid UTF8 in output of {}", description))
+    })
+}
+
+/// The target triple being compiled for, as specified by the `TARGET` environment variable.
+pub fn target_from_env() -> Option<String> {
+    env::var("TARGET").ok().filter(|target| !target.is_empty())
+}
+
+/// The flags passed to the compiler,
+/// from `CARGO_ENCODED_RUSTFLAGS` or falling back to `RUSTFLAGS`.
+pub fn rustflags_from_env() -> Vec<String> {
+    parse_rustflags(
+        env::var("CARGO_ENCODED_RUSTFLAGS").ok().as_ref().map(String::as_str),
+        env::var("RUSTFLAGS").ok().as_ref().map(String::as_str),
+    )
+}
+
+/// The options given to each `-Z` flag,
+/// accepting both `-Zname=value` and `-Z name=value`.
+pub fn unstable_flags(rustflags: &[String]) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut flags = rustflags.iter().map(String::as_str);
+    while let Some(flag) = flags.next() {
+        match flag {
+            "-Z" => result.extend(flags.next()),
+            _ if flag.starts_with("-Z") => result.push(&flag[2..]),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Parse the values of `CARGO_ENCODED_RUSTFLAGS` and `RUSTFLAGS`,
+/// preferring the former.
+pub fn parse_rustflags(encoded: Option<&str>, plain: Option<&str>) -> Vec<String> {
+    match encoded {
+        Some("") => Vec::new(),
+        Some(flags) => flags.split('\x1f').map(String::from).collect(),
+        None => plain
+            .unwrap_or("")
+            .split_whitespace()
+            .map(String::from)
+            .collect(),
+    }
+}
+
+/// Options controlling how the version is determined.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Try reading the version from rustup metadata before spawning rustc.
+    pub rustup_fast_path: bool,
+    /// Recover a best-effort version from unexpected output, instead of failing.
+    pub lenient: bool,
+}
+
+/// Information about the compiler, determined from its version output.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompilerInfo {
+    pub version: RustVersion,
+    pub kind: CompilerKind,
+    /// Extra annotations added by a vendor or distribution, like `Fedora 1.75.0-1.fc39`.
+    pub vendor_info: Option<String>,
+    pub confidence: ParseConfidence,
+    /// Unexpected parts of the output, which were skipped by lenient parsing.
+    pub oddities: Vec<String>,
+    /// The tools noticed while running the compiler, like `clippy-driver`.
+    pub tools: ToolContext,
+}
+
+impl CompilerInfo {
+    fn rustc(version: RustVersion) -> CompilerInfo {
+        CompilerInfo {
+            version,
+            kind: CompilerKind::Rustc,
+            vendor_info: None,
+            confidence: ParseConfidence::Exact,
+            oddities: Vec::new(),
+            tools: ToolContext::default(),
+        }
+    }
+}
+
+/// Determine the version, recording each command which is run.
+pub fn determine_version(
+    options: &Options,
+    attempts: &mut Vec<Attempt>,
+) -> Result<CompilerInfo, VersionDetectionError> {
+    let compiler = Rustc::from_env();
+
+    let mut fast_path = None;
+    if options.rustup_fast_path {
+        fast_path = rustup::determine_version(&compiler);
+    }
+
+    let mut info = match fast_path {
+        Some(version) => CompilerInfo::rustc(version),
+        None => query_compiler_recorded(&compiler, None, options.lenient, attempts)?,
+    };
+    if info.kind == CompilerKind::Rustc {
+        info.kind = codegen_backend_kind(&rustflags_from_env());
+    }
+    Ok(info)
+}
+
+/// Determine the kind of compiler from the `-Zcodegen-backend` flag (if any).
+fn codegen_backend_kind(rustflags: &[String]) -> CompilerKind {
+    // the last occurrence wins
+    let backend = unstable_flags(rustflags)
+        .into_iter()
+        .rev()
+        .find(|option| option.starts_with("codegen-backend="))
+        .map(|option| &option["codegen-backend=".len()..]);
+    // the backend is either a name or the path to a library, like `librustc_codegen_gcc.so`
+    match backend {
+        Some(backend) if backend.contains("gcc") => CompilerKind::RustcCodegenGcc,
+        Some(backend) if backend.contains("cranelift") => CompilerKind::RustcCodegenCranelift,
+        _ => CompilerKind::Rustc,
+    }
+}
+
+/// Determine the version of the compiler cargo would use when building in the specified directory.
+pub fn determine_version_in(dir: &Path) -> Result<RustVersion, VersionDetectionError> {
+    let compiler = config::resolve_compiler(dir)?;
+    // the rustup proxy selects the toolchain based on the current directory
+    query_version(&compiler, Some(dir))
+}
+
+/// Run the compiler with `--version` and parse the result.
+pub fn query_version(
+    compiler: &Rustc,
+    current_dir: Option<&Path>,
+) -> Result<RustVersion, VersionDetectionError> {
+    query_compiler(compiler, current_dir, false).map(|info| info.version)
+}
+
+/// Run the compiler with `--version`, determining the version and other information.
+///
+/// The codegen backend is not considered.
+/// If `lenient` is set, unexpected output is parsed on a best-effort basis.
+pub fn query_compiler(
+    compiler: &Rustc,
+    current_dir: Option<&Path>,
+    lenient: bool,
+) -> Result<CompilerInfo, VersionDetectionError> {
+    query_compiler_recorded(compiler, current_dir, lenient, &mut Vec::new())
+}
+
+/// Run the compiler with `--version`, recording each command which is run.
+pub fn query_compiler_recorded(
+    compiler: &Rustc,
+    current_dir: Option<&Path>,
+    lenient: bool,
+    attempts: &mut Vec<Attempt>,
+) -> Result<CompilerInfo, VersionDetectionError> {
+    let rustc = &compiler.rustc;
     let mut is_clippy_driver = false;
     let mut is_mirai = false;
     loop {
-        let mut command;
+        let mut args: Vec<OsString>;
         if is_mirai {
-            command = Command::new(&rustc);
+            args = vec![rustc.clone()];
         } else {
-            let mut wrapped_rustc = wrapped_rustc.clone();
-            command = Command::new(wrapped_rustc.next().unwrap());
-            command.args(wrapped_rustc);
+            args = compiler.programs().cloned().collect();
         }
+        let program = args.remove(0);
         if is_clippy_driver {
-            command.arg("--rustc");
+            args.push(OsString::from("--rustc"));
+        }
+        args.push(OsString::from("--version"));
+
+        let mut command = Command::new(&program);
+        command.args(&args);
+        if let Some(dir) = current_dir {
+            command.current_dir(dir);
         }
-        command.arg("--version");
 
         // Allow wrapper scripts or alternate compilers to tell that this is
         // `rustversion` running --version, so that they can stick to rustc's
         // version format. https://github.com/dtolnay/rustversion/issues/67
-        command.env("RUSTVERSION", "1");
+        let env = vec![(String::from("RUSTVERSION"), String::from("1"))];
+        for (key, value) in &env {
+            command.env(key, value);
+        }
 
-        let output = match command.output() {
+        let start = Instant::now();
+        let output = command.output();
+        let mut attempt = Attempt::new(program, args, env, start.elapsed());
+        let output = match output {
             Ok(output) => output,
             Err(e) => {
+                attempt.outcome = AttemptOutcome::SpawnFailed(e.to_string());
+                attempts.push(attempt);
                 let rustc = rustc.to_string_lossy();
                 return Err(VersionDetectionError::with_cause(
                     format!("Error: failed to run `{} --version`", rustc),
@@ -43,10 +352,15 @@ pub fn determine_version() -> Result<RustVersion, VersionDetectionError> {
                 ));
             }
         };
+        attempt.status = Some(output.status);
+        attempt.stderr = String::from_utf8_lossy(&output.stderr).into_owned();
 
         let string = match String::from_utf8(output.stdout) {
             Ok(string) => string,
-            Err(_e) => {
+            Err(e) => {
+                attempt.stdout = String::from_utf8_lossy(e.as_bytes()).into_owned();
+                attempt.outcome = AttemptOutcome::InvalidUtf8;
+                attempts.push(attempt);
                 let rustc = rustc.to_string_lossy();
                 return Err(crate::VersionDetectionError::new(format!(
                     "Error: Invalid UTF8 in output of `{} --version`",
@@ -54,26 +368,112 @@ pub fn determine_version() -> Result<RustVersion, VersionDetectionError> {
                 )));
             }
         };
+        attempt.stdout.clone_from(&string);
 
-        return match rustc::parse(&string) {
-            rustc::ParseResult::Success(version) => Ok(version),
+        let result = match rustc::parse(&string) {
+            rustc::ParseResult::Success(version) => Ok(CompilerInfo {
+                vendor_info: rustc::parse_vendor_info(&string),
+                ..CompilerInfo::rustc(version)
+            }),
+            rustc::ParseResult::Alternative(kind, version) => Ok(CompilerInfo {
+                kind,
+                ..CompilerInfo::rustc(version)
+            }),
             rustc::ParseResult::OopsClippy if !is_clippy_driver => {
-                is_clippy_driver = true;
-                continue;
+                if let Some(version) = crate::clippy::determine_rust_version(compiler, &string) {
+                    let mut info = CompilerInfo::rustc(version);
+                    info.tools.clippy = true;
+                    Ok(info)
+                } else {
+                    attempt.outcome = AttemptOutcome::RetryClippy;
+                    attempts.push(attempt);
+                    is_clippy_driver = true;
+                    continue;
+                }
             },
-            rustc::ParseResult::OopsMirai if !is_mirai && rustc_wrapper.is_some() => {
+            rustc::ParseResult::OopsMirai if !is_mirai && compiler.has_wrapper() => {
+                attempt.outcome = AttemptOutcome::RetryMirai;
+                attempts.push(attempt);
                 is_mirai = true;
                 continue;
             },
             rustc::ParseResult::Unrecognized
             | rustc::ParseResult::OopsClippy
             | rustc::ParseResult::OopsMirai => {
-                return Err(crate::VersionDetectionError::new(format!(
-                    "Error: unexpected output from `rustc --version`: {:?}\n\n\
-                    Please file an issue in https://github.com/Techcable/rustversion-detect",
-                    string
-                )));
+                let lenient_result = if lenient { rustc::parse_lenient(&string) } else { None };
+                match lenient_result {
+                    Some((version, oddities)) => Ok(CompilerInfo {
+                        confidence: ParseConfidence::BestEffort,
+                        oddities,
+                        ..CompilerInfo::rustc(version)
+                    }),
+                    None => Err(crate::VersionDetectionError::new(format!(
+                        "Error: unexpected output from `rustc --version`: {:?}\n\n\
+                        Please file an issue in https://github.com/Techcable/rustversion-detect",
+                        string
+                    ))),
+                }
+            }
+        };
+        attempt.outcome = match result {
+            Ok(ref info) if info.confidence == ParseConfidence::BestEffort => {
+                AttemptOutcome::BestEffort(info.version)
             }
+            Ok(ref info) => AttemptOutcome::Parsed(info.version),
+            Err(_) => AttemptOutcome::Unrecognized,
+        };
+        attempts.push(attempt);
+        return result.map(|mut info| {
+            info.tools.clippy |= is_clippy_driver;
+            info.tools.mirai |= is_mirai;
+            info
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{codegen_backend_kind, ENV_VARS};
+    use crate::CompilerKind;
+
+    #[test]
+    fn env_vars() {
+        let mut expected = vec![
+            // `Rustc::from_env`
+            "RUSTC",
+            "RUSTC_WRAPPER",
+            // the rustup proxy
+            "RUSTUP_TOOLCHAIN",
+            "RUSTUP_HOME",
+            // `UnstableEnv::from_env` and `rustflags_from_env`
+            "RUSTC_BOOTSTRAP",
+            "CARGO_ENCODED_RUSTFLAGS",
+            "RUSTFLAGS",
+            // `Rustc::rustdoc_from_env`
+            "RUSTDOC",
+            "RUSTDOC_WRAPPER",
+        ];
+        let mut actual = ENV_VARS.to_vec();
+        expected.sort_unstable();
+        actual.sort_unstable();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn codegen_backend() {
+        let kind = |flags: &[&str]| {
+            codegen_backend_kind(&flags.iter().map(|&flag| String::from(flag)).collect::<Vec<_>>())
         };
+        assert_eq!(kind(&[]), CompilerKind::Rustc);
+        assert_eq!(kind(&["-Zcodegen-backend=gcc"]), CompilerKind::RustcCodegenGcc);
+        assert_eq!(
+            kind(&["-Z", "codegen-backend=/opt/librustc_codegen_gcc.so"]),
+            CompilerKind::RustcCodegenGcc
+        );
+        assert_eq!(
+            kind(&["-Zcodegen-backend=gcc", "-Zcodegen-backend=cranelift"]),
+            CompilerKind::RustcCodegenCranelift
+        );
+        assert_eq!(kind(&["-Zcodegen-backend=llvm"]), CompilerKind::Rustc);
     }
 }
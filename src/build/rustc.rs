@@ -1,7 +1,9 @@
-use crate::{Date, RustVersion};
+use crate::{CompilerKind, Date, RustVersion};
 
 pub enum ParseResult {
     Success(RustVersion),
+    /// An alternative compiler, with the closest equivalent rustc version.
+    Alternative(CompilerKind, RustVersion),
     OopsClippy,
     OopsMirai,
     Unrecognized,
@@ -12,15 +14,210 @@ pub fn parse(string: &str) -> ParseResult {
     let mut words = last_line.trim().split(' ');
 
     match words.next() {
-        Some("rustc") => {}
+        Some("rustc") | Some("rustdoc") => {}
         Some(word) if word.starts_with("clippy") => return ParseResult::OopsClippy,
         Some("mirai") => return ParseResult::OopsMirai,
-        Some(_) | None => return ParseResult::Unrecognized,
+        Some(_) | None => {
+            return match parse_alternative(string) {
+                Some((kind, version)) => ParseResult::Alternative(kind, version),
+                None => ParseResult::Unrecognized,
+            };
+        }
     }
 
     parse_words(&mut words).map_or(ParseResult::Unrecognized, ParseResult::Success)
 }
 
+/// Recognize the `--version` output of an alternative compiler,
+/// which is printed on the first line.
+fn parse_alternative(string: &str) -> Option<(CompilerKind, RustVersion)> {
+    let first_line = string.lines().next()?;
+    let mut words = first_line.trim().split(' ');
+    let name = words.next()?.to_ascii_lowercase();
+    match &*name {
+        // gccrs (GCC) 14.1.0
+        //
+        // gccrs targets the language as of rust 1.49
+        "gccrs" => Some((CompilerKind::Gccrs, RustVersion::stable(1, 49, 0))),
+        // MRustC v0.11.2 (master:abcdef0)
+        "mrustc" => {
+            let version = words.find(|word| word.starts_with('v'))?;
+            let mut digits = version[1..].split('.');
+            let major = digits.next()?.parse::<u32>().ok()?;
+            let minor = digits.next()?.parse::<u32>().ok()?;
+            // the version of rustc whose standard library can be compiled
+            let rustc_minor = match (major, minor) {
+                (0, 0..=9) => 29,
+                (0, 10) => 54,
+                _ => 74,
+            };
+            Some((CompilerKind::Mrustc, RustVersion::stable(1, rustc_minor, 0)))
+        }
+        _ => None,
+    }
+}
+
+/// Find the extra groups which vendors and distributions add to the output of `rustc --version`,
+/// like `rustc 1.75.0 (82e1608df 2023-12-21) (Fedora 1.75.0-1.fc39)`.
+///
+/// The commit hash and date are not included,
+/// nor is the build date printed by old versions of rustc.
+/// Multiple groups are joined by `", "`.
+pub fn parse_vendor_info(string: &str) -> Option<String> {
+    let last_line = string.lines().last().unwrap_or(string).trim();
+    // skip the name and version
+    let rest = last_line.splitn(3, ' ').nth(2)?;
+
+    let mut groups = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in rest.char_indices() {
+        match c {
+            '(' => {
+                if depth == 0 {
+                    start = index + 1;
+                }
+                depth += 1;
+            }
+            ')' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    groups.push(rest[start..index].trim());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let vendor_groups = groups
+        .iter()
+        .enumerate()
+        .filter(|&(index, group)| !(index == 0 && is_commit_group(group)))
+        .map(|(_, &group)| group)
+        .filter(|group| !group.is_empty() && !is_build_date_group(group))
+        .collect::<Vec<_>>();
+    if vendor_groups.is_empty() {
+        None
+    } else {
+        Some(vendor_groups.join(", "))
+    }
+}
+
+/// Check for a group like `82e1608df 2023-12-21`.
+fn is_commit_group(group: &str) -> bool {
+    let mut words = group.split(' ');
+    match (words.next(), words.next(), words.next()) {
+        (Some(hash), Some(date), None) => {
+            hash.chars().all(|c| c.is_ascii_hexdigit()) && date.parse::<Date>().is_ok()
+        }
+        _ => false,
+    }
+}
+
+/// Check for a group like `built 2015-05-14`, printed by rustc 1.0.
+fn is_build_date_group(group: &str) -> bool {
+    group.starts_with("built ") && group["built ".len()..].parse::<Date>().is_ok()
+}
+
+/// Parse the first line of `<tool> --version` or `<tool> -vV`,
+/// for tools like cargo which use the same format as rustc.
+pub fn parse_tool(string: &str, tool: &str) -> Option<RustVersion> {
+    let first_line = string.lines().next().unwrap_or(string);
+    let mut words = first_line.trim().split(' ');
+    if words.next()? != tool {
+        return None;
+    }
+    parse_words(&mut words)
+}
+
+/// Parse the output of `rustc --version`, recovering from unexpected formats.
+///
+/// Every line is scanned for a version, preferring the last.
+/// An unknown channel suffix is treated as a development build.
+/// Returns the version along with descriptions of the oddities that were skipped.
+pub fn parse_lenient(string: &str) -> Option<(RustVersion, Vec<String>)> {
+    let lines = string
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    for (index, line) in lines.iter().enumerate().rev() {
+        let mut oddities = Vec::new();
+        let version = match parse_line_lenient(line, &mut oddities) {
+            Some(version) => version,
+            None => continue,
+        };
+        let skipped = lines.len() - index - 1;
+        if skipped > 0 {
+            oddities.push(format!("skipped {} line(s) after the version", skipped));
+        }
+        return Some((version, oddities));
+    }
+    None
+}
+
+fn parse_line_lenient(line: &str, oddities: &mut Vec<String>) -> Option<RustVersion> {
+    use crate::Channel::{Stable, Development, Beta, Nightly};
+
+    let mut words = line.split_whitespace();
+    match words.next()? {
+        "rustc" | "rustdoc" => {}
+        _ => return None,
+    }
+    let version_channel = words.next()?;
+    let (version, channel) = match version_channel.find('-') {
+        Some(index) => (&version_channel[..index], Some(&version_channel[index + 1..])),
+        None => (version_channel, None),
+    };
+
+    let mut digits = version.split('.');
+    let major = digits.next()?.parse().ok()?;
+    let minor = digits.next()?.parse().ok()?;
+    let patch = digits.next().unwrap_or("0").parse().ok()?;
+
+    let channel = match channel {
+        None => Stable,
+        Some("dev") => Development,
+        Some(channel) if channel.starts_with("beta") => Beta {
+            number: channel["beta".len()..]
+                .trim_start_matches('.')
+                .parse()
+                .ok(),
+        },
+        Some("nightly") => {
+            let mut commit = words.peekable();
+            if commit.peek().map_or(false, |word| !word.starts_with('(')) {
+                oddities.push(String::from("nightly commit information is not in parentheses"));
+            }
+            let date = commit
+                .map(|word| word.trim_matches(|c| c == '(' || c == ')'))
+                .find_map(|word| word.parse::<Date>().ok());
+            if let Some(date) = date {
+                Nightly { date }
+            } else {
+                oddities.push(String::from(
+                    "nightly version without a commit date, assuming a development build",
+                ));
+                Development
+            }
+        }
+        Some(other) => {
+            oddities.push(format!(
+                "unknown channel suffix `{}`, assuming a development build",
+                other
+            ));
+            Development
+        }
+    };
+
+    Some(RustVersion {
+        major,
+        minor,
+        patch,
+        channel,
+    })
+}
+
 fn parse_words(words: &mut dyn Iterator<Item = &str>) -> Option<RustVersion> {
     use crate::Channel::{Stable, Development, Beta, Nightly};
 
@@ -36,7 +233,13 @@ fn parse_words(words: &mut dyn Iterator<Item = &str>) -> Option<RustVersion> {
     let channel = match channel {
         None => Stable,
         Some("dev") => Development,
-        Some(channel) if channel.starts_with("beta") => Beta,
+        Some(channel) if channel.starts_with("beta") => Beta {
+            // like `beta.3`, although the number is missing from some builds
+            number: channel["beta".len()..]
+                .trim_start_matches('.')
+                .parse()
+                .ok(),
+        },
         Some("nightly") => match words.next() {
             Some(hash) if hash.starts_with('(') => match words.next() {
                 None if hash.ends_with(')') => Development,
@@ -105,7 +308,7 @@ mod test {
                     major: 1,
                     minor: 35,
                     patch: 0,
-                    channel: Beta,
+                    channel: Beta { number: Some(3) },
                 },
             ),
             (
@@ -171,10 +374,131 @@ mod test {
         for (string, expected) in cases {
             match parse(string) {
                 ParseResult::Success(version) => assert_eq!(version, *expected),
-                ParseResult::OopsClippy | ParseResult::OopsMirai | ParseResult::Unrecognized => {
+                ParseResult::Alternative(..)
+                | ParseResult::OopsClippy
+                | ParseResult::OopsMirai
+                | ParseResult::Unrecognized => {
                     panic!("unrecognized: {:?}", string);
                 }
             }
         }
     }
 }
+
+/// Tests for the parsing added by this crate, which rustversion does not support.
+#[cfg(test)]
+mod test_extensions {
+    use super::*;
+
+    #[test]
+    fn test_parse_vendor_info() {
+        let cases = &[
+            ("rustc 1.75.0 (82e1608df 2023-12-21)", None),
+            (
+                "rustc 1.75.0 (82e1608df 2023-12-21) (Fedora 1.75.0-1.fc39)",
+                Some("Fedora 1.75.0-1.fc39"),
+            ),
+            ("rustc 1.0.0 (a59de37e9 2015-05-13) (built 2015-05-14)", None),
+            ("rustc 1.52.1-nightly (gentoo)", Some("gentoo")),
+            (
+                "rustc 1.76.0 (07dca489a 2024-02-04) (Ferrocene by Ferrous Systems)",
+                Some("Ferrocene by Ferrous Systems"),
+            ),
+            ("rustc 1.18.0", None),
+        ];
+        for &(text, expected) in cases {
+            let vendor_info = parse_vendor_info(text);
+            assert_eq!(vendor_info.as_ref().map(String::as_str), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        use crate::Channel::*;
+
+        let cases = &[
+            ("rustc 1.80.0-custom (abc 2024-06-01)", RustVersion {
+                major: 1,
+                minor: 80,
+                patch: 0,
+                channel: Development,
+            }, 1),
+            ("rustc 1.80.0-nightly abc 2024-06-01", RustVersion {
+                major: 1,
+                minor: 80,
+                patch: 0,
+                channel: Nightly { date: Date::new(2024, 6, 1) },
+            }, 1),
+            ("rustc 1.80.0-nightly (abc unknown)", RustVersion {
+                major: 1,
+                minor: 80,
+                patch: 0,
+                channel: Development,
+            }, 1),
+            (
+                "rustc 1.80.1 (abc 2024-06-01)\nsome trailing output\n",
+                RustVersion::stable(1, 80, 1),
+                1,
+            ),
+        ];
+        for &(text, expected, num_oddities) in cases {
+            match parse(text) {
+                ParseResult::Unrecognized => {}
+                _ => panic!("Strict parsing accepted {:?}", text),
+            }
+            let (version, oddities) = parse_lenient(text).unwrap();
+            assert_eq!(version, expected, "{:?}", text);
+            assert_eq!(oddities.len(), num_oddities, "{:?}: {:?}", text, oddities);
+        }
+        assert!(parse_lenient("gibberish\n").is_none());
+    }
+
+    #[test]
+    fn test_parse_alternative() {
+        let cases = &[
+            (
+                "gccrs (GCC) 14.1.0\nCopyright (C) 2024 Free Software Foundation, Inc.\n",
+                CompilerKind::Gccrs,
+                RustVersion::stable(1, 49, 0),
+            ),
+            (
+                "MRustC v0.10.1 (HEAD:d3e4c5f)",
+                CompilerKind::Mrustc,
+                RustVersion::stable(1, 54, 0),
+            ),
+            (
+                "MRustC v0.11.2 (master:0a1b2c3)",
+                CompilerKind::Mrustc,
+                RustVersion::stable(1, 74, 0),
+            ),
+        ];
+        for &(text, expected_kind, expected_version) in cases {
+            match parse(text) {
+                ParseResult::Alternative(kind, version) => {
+                    assert_eq!(kind, expected_kind, "{:?}", text);
+                    assert_eq!(version, expected_version, "{:?}", text);
+                }
+                _ => panic!("Failed to parse {:?}", text),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_rustdoc() {
+        let version = "rustdoc 1.80.0-nightly (ada5e2c7b 2024-05-31)";
+        match parse(version) {
+            ParseResult::Success(parsed) => assert_eq!(
+                parsed,
+                RustVersion {
+                    major: 1,
+                    minor: 80,
+                    patch: 0,
+                    channel: crate::Channel::Nightly {
+                        date: Date::new(2024, 5, 31),
+                    },
+                }
+            ),
+            _ => panic!("Failed to parse {:?}", version),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Reads the version from rustup metadata, avoiding the need to spawn rustc.
+
+use crate::build::{self, rustc, Rustc};
+use crate::version::RustVersion;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The location of rustup's data, from `RUSTUP_HOME` or defaulting to `~/.rustup`.
+pub fn rustup_home() -> Option<PathBuf> {
+    if let Some(home) = env::var_os("RUSTUP_HOME").filter(|home| !home.is_empty()) {
+        return Some(PathBuf::from(home));
+    }
+    Some(build::home_dir()?.join(".rustup"))
+}
+
+/// The directory of the active toolchain, as specified by `RUSTUP_TOOLCHAIN`.
+///
+/// This is either the name of an installed toolchain or the path to a custom toolchain.
+pub fn active_toolchain_dir() -> Option<PathBuf> {
+    let toolchain = PathBuf::from(env::var_os("RUSTUP_TOOLCHAIN").filter(|name| !name.is_empty())?);
+    if toolchain.is_absolute() {
+        return Some(toolchain);
+    }
+    Some(rustup_home()?.join("toolchains").join(toolchain))
+}
+
+/// Determine the version from the metadata of the active rustup toolchain.
+///
+/// Returns `None` if a wrapper is set,
+/// if `RUSTC` does not refer to the active toolchain,
+/// or if anything about the metadata is unexpected.
+pub fn determine_version(compiler: &Rustc) -> Option<RustVersion> {
+    if compiler.has_wrapper() {
+        return None;
+    }
+    let toolchain_dir = active_toolchain_dir()?;
+    let rustc = Path::new(&compiler.rustc);
+    if rustc != Path::new("rustc") {
+        let expected = toolchain_dir.join("bin").join(format!("rustc{}", env::consts::EXE_SUFFIX));
+        if !same_file(rustc, &expected) {
+            return None;
+        }
+    }
+    read_manifest_version(&toolchain_dir)
+}
+
+/// The rustc version of the active toolchain, if `program` belongs to it.
+///
+/// A program specified without a directory is assumed to be a rustup proxy,
+/// which runs the active toolchain.
+pub fn active_toolchain_version(program: &Path) -> Option<RustVersion> {
+    let toolchain_dir = active_toolchain_dir()?;
+    if program.components().count() > 1 {
+        let bin_dir = toolchain_dir.join("bin");
+        if !same_file(program.parent()?, &bin_dir) {
+            return None;
+        }
+    }
+    read_manifest_version(&toolchain_dir)
+}
+
+/// Read the rustc version from `lib/rustlib/multirust-channel-manifest.toml` in the toolchain directory.
+pub fn read_manifest_version(toolchain_dir: &Path) -> Option<RustVersion> {
+    let manifest = toolchain_dir
+        .join("lib")
+        .join("rustlib")
+        .join("multirust-channel-manifest.toml");
+    parse_manifest(&fs::read_to_string(manifest).ok()?)
+}
+
+/// Find the `version` of the `[pkg.rustc]` table,
+/// which has the same format as the output of `rustc --version`.
+fn parse_manifest(text: &str) -> Option<RustVersion> {
+    let mut in_rustc_table = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_rustc_table = line == "[pkg.rustc]";
+        } else if in_rustc_table && line.starts_with("version") {
+            let value = line["version".len()..].trim_start();
+            if !value.starts_with('=') {
+                continue;
+            }
+            let value = value[1..].trim();
+            if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+                return None;
+            }
+            return match rustc::parse(&format!("rustc {}", &value[1..value.len() - 1])) {
+                rustc::ParseResult::Success(version) => Some(version),
+                _ => None,
+            };
+        }
+    }
+    None
+}
+
+fn same_file(first: &Path, second: &Path) -> bool {
+    match (first.canonicalize(), second.canonicalize()) {
+        (Ok(first), Ok(second)) => first == second,
+        _ => first == second,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_manifest;
+    use crate::{Channel, Date, RustVersion};
+
+    #[test]
+    fn manifest() {
+        let text = r#"manifest-version = "2"
+date = "2026-05-20"
+
+[pkg.cargo]
+version = "0.98.0-nightly (e50aa6fba 2026-05-19)"
+
+[pkg.rustc]
+version = "1.97.0-nightly (e50aa6fba 2026-05-19)"
+
+[pkg.rustc.target.aarch64-apple-darwin]
+available = true
+"#;
+        assert_eq!(
+            parse_manifest(text),
+            Some(RustVersion {
+                major: 1,
+                minor: 97,
+                patch: 0,
+                channel: Channel::Nightly {
+                    date: Date::new(2026, 5, 19),
+                },
+            })
+        );
+        assert_eq!(
+            parse_manifest("[pkg.rustc]\nversion = \"1.95.0 (59807616e 2026-04-14)\"\n"),
+            Some(RustVersion::stable(1, 95, 0))
+        );
+        assert_eq!(parse_manifest("[pkg.cargo]\nversion = \"0.96.0\"\n"), None);
+    }
+}
@@ -0,0 +1,206 @@
+//! Detects the version of cargo, which can differ from the version of rustc.
+//!
+//! Some build script features depend on cargo rather than the compiler,
+//! like `cargo::` directives, `cargo::rustc-check-cfg` and `CARGO_PKG_RUST_VERSION`.
+//!
+//! # Example
+//! ```no_run
+//! // in build.rs
+//! let cargo = rustversion_detect::detect_cargo_version().unwrap();
+//! if cargo.is_since_minor_version(1, 77) {
+//!     println!("cargo::rustc-check-cfg=cfg(has_feature)");
+//! }
+//! ```
+
+use std::env;
+use std::ffi::OsString;
+
+use crate::build::{self, rustc};
+use crate::{state, Date, RustVersion, VersionDetectionError};
+
+/// Detect the version of cargo, by executing `cargo --version`.
+///
+/// Cargo sets the `CARGO` environment variable for build scripts,
+/// falling back to `cargo` if that is not set.
+/// The result uses the same [`Channel`](crate::Channel) logic as the compiler version,
+/// so a nightly cargo has the date of its commit.
+///
+/// The result is cached for future calls.
+///
+/// # Errors
+/// Returns an error if unable to execute cargo or parse its output.
+pub fn detect_cargo_version() -> Result<RustVersion, VersionDetectionError> {
+    if let Some(cached) = *state::read(state::cargo_version_cache()) {
+        return Ok(cached);
+    }
+    let output = build::run_tool(&cargo_program(), &["--version"])?;
+    let version = rustc::parse_tool(&output, "cargo")
+        .ok_or_else(|| unexpected_output(&output, "--version"))?;
+    *state::write(state::cargo_version_cache()) = Some(version);
+    Ok(version)
+}
+
+/// Detect the version of cargo along with build information,
+/// by executing `cargo -vV`.
+///
+/// Behaves like [`detect_cargo_version`], but gives access to the commit and host triple.
+///
+/// The result is cached for future calls.
+///
+/// # Errors
+/// Returns an error if unable to execute cargo or parse its output.
+pub fn detect_cargo_version_verbose() -> Result<VerboseVersion, VersionDetectionError> {
+    if let Some(ref cached) = *state::read(state::cargo_verbose_version_cache()) {
+        return Ok(cached.clone());
+    }
+    let output = build::run_tool(&cargo_program(), &["-vV"])?;
+    let version =
+        VerboseVersion::parse(&output, "cargo").ok_or_else(|| unexpected_output(&output, "-vV"))?;
+    *state::write(state::cargo_verbose_version_cache()) = Some(version.clone());
+    Ok(version)
+}
+
+fn cargo_program() -> OsString {
+    env::var_os("CARGO")
+        .filter(|cargo| !cargo.is_empty())
+        .unwrap_or_else(|| OsString::from("cargo"))
+}
+
+fn unexpected_output(output: &str, flag: &str) -> VersionDetectionError {
+    VersionDetectionError::new(format!(
+        "Error: unexpected output from `cargo {}`: {:?}\n\n\
+        Please file an issue in https://github.com/Techcable/rustversion-detect",
+        flag, output
+    ))
+}
+
+/// The version of a tool along with build information, as printed by `-vV`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VerboseVersion {
+    version: RustVersion,
+    commit_hash: Option<String>,
+    commit_date: Option<Date>,
+    host: Option<String>,
+}
+impl VerboseVersion {
+    /// Parse the output of `<tool> -vV`.
+    ///
+    /// The first line is parsed like `<tool> --version`,
+    /// with the remaining lines in the format `key: value`.
+    pub(crate) fn parse(text: &str, tool: &str) -> Option<VerboseVersion> {
+        let version = rustc::parse_tool(text, tool)?;
+        let mut result = VerboseVersion {
+            version,
+            commit_hash: None,
+            commit_date: None,
+            host: None,
+        };
+        for line in text.lines().skip(1) {
+            let index = match line.find(':') {
+                Some(index) => index,
+                None => continue,
+            };
+            let value = line[index + 1..].trim();
+            // the value is `unknown` if built without git information
+            if value.is_empty() || value == "unknown" {
+                continue;
+            }
+            match line[..index].trim() {
+                "commit-hash" => result.commit_hash = Some(value.into()),
+                "commit-date" => result.commit_date = value.parse().ok(),
+                "host" => result.host = Some(value.into()),
+                _ => {}
+            }
+        }
+        Some(result)
+    }
+
+    /// The version of the tool.
+    #[inline]
+    #[must_use]
+    pub fn version(&self) -> &RustVersion {
+        &self.version
+    }
+
+    /// The full hash of the commit the tool was built from, if known.
+    #[inline]
+    #[must_use]
+    pub fn commit_hash(&self) -> Option<&str> {
+        self.commit_hash.as_ref().map(String::as_str)
+    }
+
+    /// The date of the commit the tool was built from, if known.
+    #[inline]
+    #[must_use]
+    pub fn commit_date(&self) -> Option<Date> {
+        self.commit_date
+    }
+
+    /// The host triple the tool was built for, like `x86_64-unknown-linux-gnu`.
+    #[inline]
+    #[must_use]
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_ref().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::VerboseVersion;
+    use crate::build::rustc;
+    use crate::{Channel, Date, RustVersion};
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            rustc::parse_tool("cargo 1.78.0 (54d8815d0 2024-03-26)\n", "cargo"),
+            Some(RustVersion::stable(1, 78, 0))
+        );
+        assert_eq!(
+            rustc::parse_tool("cargo 1.97.0-nightly (a1b2c3d4e 2026-05-12)", "cargo"),
+            Some(RustVersion {
+                major: 1,
+                minor: 97,
+                patch: 0,
+                channel: Channel::Nightly {
+                    date: Date::new(2026, 5, 12),
+                },
+            })
+        );
+        assert_eq!(rustc::parse_tool("rustc 1.78.0", "cargo"), None);
+    }
+
+    #[test]
+    fn parse_verbose() {
+        let text = "cargo 1.95.0 (f2d3ce0bd 2026-03-21)
+release: 1.95.0
+commit-hash: f2d3ce0bd7f24a49f8f72d9000448f8838c4e850
+commit-date: 2026-03-21
+host: x86_64-unknown-linux-gnu
+libgit2: 1.9.2 (sys:0.20.4 vendored)
+os: Debian 12.0.0 (bookworm) [64-bit]
+";
+        let verbose = VerboseVersion::parse(text, "cargo").unwrap();
+        assert_eq!(*verbose.version(), RustVersion::stable(1, 95, 0));
+        assert_eq!(
+            verbose.commit_hash(),
+            Some("f2d3ce0bd7f24a49f8f72d9000448f8838c4e850")
+        );
+        assert_eq!(verbose.commit_date(), Some(Date::new(2026, 3, 21)));
+        assert_eq!(verbose.host(), Some("x86_64-unknown-linux-gnu"));
+
+        let verbose =
+            VerboseVersion::parse("cargo 1.20.0\ncommit-hash: unknown\n", "cargo").unwrap();
+        assert_eq!(verbose.commit_hash(), None);
+        // older versions of cargo do not print the host
+        assert_eq!(verbose.host(), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // miri cannot spawn processes
+    fn detect() {
+        let version = super::detect_cargo_version().unwrap();
+        let verbose = super::detect_cargo_version_verbose().unwrap();
+        assert_eq!(*verbose.version(), version);
+    }
+}
@@ -0,0 +1,223 @@
+//! Parses the version of clippy, as printed by `clippy-driver --version`.
+//!
+//! Clippy versions have the format `0.1.N`, where `N` is the minor version of rust.
+//! The output does not include the channel, so the rust version cannot always be determined.
+
+use core::fmt::{self, Display};
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+
+use crate::build::{self, rustup, Rustc};
+use crate::{state, Channel, Date, RustVersion, StableVersionSpec, VersionDetectionError};
+
+/// The version of clippy, like `clippy 0.1.78 (3d1b1e4f8 2024-03-16)`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClippyVersion {
+    /// The major version of clippy, which is always zero.
+    pub major: u32,
+    /// The minor version of clippy, which is always one.
+    pub minor: u32,
+    /// The patch version of clippy, which is the minor version of rust.
+    pub patch: u32,
+    /// The abbreviated hash of the rust commit clippy was built from, if known.
+    pub commit_hash: Option<String>,
+    /// The date of the rust commit clippy was built from, if known.
+    pub commit_date: Option<Date>,
+}
+impl ClippyVersion {
+    /// Parse the output of `clippy-driver --version` or `cargo clippy --version`.
+    pub(crate) fn parse(text: &str) -> Option<ClippyVersion> {
+        let last_line = text.lines().last().unwrap_or(text);
+        let mut words = last_line.trim().split(' ');
+        if words.next()? != "clippy" {
+            return None;
+        }
+        let mut digits = words.next()?.split('.');
+        let major = digits.next()?.parse().ok()?;
+        let minor = digits.next()?.parse().ok()?;
+        let patch = digits.next()?.parse().ok()?;
+        let mut result = ClippyVersion {
+            major,
+            minor,
+            patch,
+            commit_hash: None,
+            commit_date: None,
+        };
+        if let (Some(hash), Some(date)) = (words.next(), words.next()) {
+            if hash.starts_with('(') && date.ends_with(')') {
+                result.commit_hash = Some(hash[1..].into());
+                result.commit_date = date[..date.len() - 1].parse().ok();
+            }
+        }
+        Some(result)
+    }
+
+    /// The minor version of rust which corresponds to this version of clippy.
+    ///
+    /// Clippy version `0.1.N` corresponds to rust `1.N`.
+    /// Returns `None` if the version is not in this format.
+    #[must_use]
+    pub fn rust_version(&self) -> Option<StableVersionSpec> {
+        if self.major == 0 && self.minor == 1 {
+            Some(StableVersionSpec::minor(1, self.patch))
+        } else {
+            None
+        }
+    }
+
+    /// Check if this version of clippy corresponds to the specified rust version or later.
+    ///
+    /// This is useful to gate clippy lints which were added in a specific version.
+    #[must_use]
+    pub fn is_since_rust_version(&self, major: u32, minor: u32) -> bool {
+        self.rust_version().map_or(false, |version| {
+            version.to_version().is_since_minor_version(major, minor)
+        })
+    }
+
+    /// Determine the full rust version, using the rustc version of the toolchain.
+    ///
+    /// The patch version and channel are not included in the output of clippy,
+    /// so they must be taken from the toolchain.
+    /// Returns `None` if the toolchain does not match this version of clippy.
+    pub(crate) fn to_rust_version(&self, toolchain: &RustVersion) -> Option<RustVersion> {
+        let spec = self.rust_version()?;
+        if toolchain.major != spec.major || toolchain.minor != spec.minor {
+            return None;
+        }
+        match toolchain.channel {
+            // built from a different commit
+            Channel::Nightly { date }
+                if self.commit_date.map_or(false, |commit| commit != date) =>
+            {
+                return None;
+            }
+            // the beta number is only known from `--rustc`,
+            // so use that result to stay consistent with it
+            Channel::Beta { .. } => return None,
+            _ => {}
+        }
+        Some(*toolchain)
+    }
+}
+/// Displays the version in the same format as `clippy-driver --version`.
+impl Display for ClippyVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "clippy {}.{}.{}", self.major, self.minor, self.patch)?;
+        if let (Some(ref hash), Some(ref date)) = (&self.commit_hash, &self.commit_date) {
+            write!(f, " ({} {})", hash, date)?;
+        }
+        Ok(())
+    }
+}
+
+/// Detect the version of clippy, if the crate is being checked by clippy.
+///
+/// The `cargo clippy` command runs `clippy-driver` as the `RUSTC_WORKSPACE_WRAPPER`,
+/// or as the `RUSTC_WRAPPER` in older versions.
+/// This checks `RUSTC_WRAPPER`, `RUSTC_WORKSPACE_WRAPPER` and `RUSTC` (in that order)
+/// for a program named `clippy-driver`.
+/// Returns `None` without running anything if clippy is not in use.
+///
+/// The result is cached for future calls.
+///
+/// # Example
+/// ```no_run
+/// // in build.rs
+/// println!("cargo:rustc-check-cfg=cfg(has_new_clippy_lint)");
+/// if let Some(clippy) = rustversion_detect::clippy::detect_clippy_version().unwrap() {
+///     if clippy.is_since_rust_version(1, 78) {
+///         println!("cargo:rustc-cfg=has_new_clippy_lint");
+///     }
+/// }
+/// ```
+///
+/// # Errors
+/// Returns an error if unable to execute the driver.
+pub fn detect_clippy_version() -> Result<Option<ClippyVersion>, VersionDetectionError> {
+    if let Some(ref cached) = *state::read(state::clippy_version_cache()) {
+        return Ok(cached.clone());
+    }
+    let driver = ["RUSTC_WRAPPER", "RUSTC_WORKSPACE_WRAPPER", "RUSTC"]
+        .iter()
+        .filter_map(env::var_os)
+        .find(is_clippy_driver);
+    let version = match driver {
+        Some(driver) => ClippyVersion::parse(&build::run_tool(&driver, &["--version"])?),
+        None => None,
+    };
+    *state::write(state::clippy_version_cache()) = Some(version.clone());
+    Ok(version)
+}
+
+fn is_clippy_driver(program: &OsString) -> bool {
+    Path::new(program)
+        .file_stem()
+        .map_or(false, |stem| stem == "clippy-driver")
+}
+
+/// Determine the rust version from the output of `clippy-driver --version`,
+/// avoiding the need to run it again with `--rustc`.
+///
+/// This is only possible if one of the programs is named `clippy-driver`
+/// and it belongs to the active rustup toolchain,
+/// whose manifest determines the parts of the version that clippy omits.
+pub(crate) fn determine_rust_version(compiler: &Rustc, output: &str) -> Option<RustVersion> {
+    let clippy = ClippyVersion::parse(output)?;
+    // the driver may be any of the wrappers, or the compiler itself
+    let driver = compiler
+        .programs()
+        .find(|program| is_clippy_driver(program))?;
+    let toolchain = rustup::active_toolchain_version(Path::new(driver))?;
+    clippy.to_rust_version(&toolchain)
+}
+
+#[cfg(test)]
+mod test {
+    use super::ClippyVersion;
+    use crate::{Channel, Date, RustVersion, StableVersionSpec};
+
+    #[test]
+    fn parse() {
+        let clippy = ClippyVersion::parse("clippy 0.1.78 (3d1b1e4f8 2024-03-16)\n").unwrap();
+        assert_eq!(clippy.rust_version(), Some(StableVersionSpec::minor(1, 78)));
+        assert_eq!(clippy.commit_hash.as_ref().unwrap(), "3d1b1e4f8");
+        assert_eq!(clippy.commit_date, Some(Date::new(2024, 3, 16)));
+        assert_eq!(clippy.to_string(), "clippy 0.1.78 (3d1b1e4f8 2024-03-16)");
+        assert!(clippy.is_since_rust_version(1, 78));
+        assert!(!clippy.is_since_rust_version(1, 79));
+
+        let clippy = ClippyVersion::parse("clippy 0.1.50").unwrap();
+        assert_eq!(clippy.commit_date, None);
+        assert!(ClippyVersion::parse("rustc 1.78.0").is_none());
+    }
+
+    #[test]
+    fn to_rust_version() {
+        let clippy = ClippyVersion::parse("clippy 0.1.97 (e50aa6fba4 2026-05-19)").unwrap();
+        let nightly = |day| RustVersion {
+            major: 1,
+            minor: 97,
+            patch: 0,
+            channel: Channel::Nightly {
+                date: Date::new(2026, 5, day),
+            },
+        };
+        assert_eq!(clippy.to_rust_version(&nightly(19)), Some(nightly(19)));
+        // built from a different commit
+        assert_eq!(clippy.to_rust_version(&nightly(18)), None);
+        assert_eq!(
+            clippy.to_rust_version(&RustVersion::stable(1, 97, 1)),
+            Some(RustVersion::stable(1, 97, 1))
+        );
+        assert_eq!(clippy.to_rust_version(&RustVersion::stable(1, 96, 0)), None);
+        let beta = RustVersion {
+            major: 1,
+            minor: 97,
+            patch: 0,
+            channel: Channel::Beta { number: Some(3) },
+        };
+        assert_eq!(clippy.to_rust_version(&beta), None);
+    }
+}
@@ -111,6 +111,46 @@ impl Date {
         *self < end
     }
 
+    /// Get the date of the following day.
+    ///
+    /// Accounts for the lengths of months and leap years.
+    ///
+    /// # Example
+    /// ```
+    /// # use rustversion_detect::Date;
+    /// assert_eq!(Date::new(2024, 2, 28).next_day(), Date::new(2024, 2, 29));
+    /// assert_eq!(Date::new(2023, 2, 28).next_day(), Date::new(2023, 3, 1));
+    /// assert_eq!(Date::new(2024, 12, 31).next_day(), Date::new(2025, 1, 1));
+    /// ```
+    #[must_use]
+    pub fn next_day(&self) -> Date {
+        let is_leap_year = (self.year % 4 == 0 && self.year % 100 != 0) || self.year % 400 == 0;
+        let days_in_month = match self.month {
+            2 if is_leap_year => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        if self.day < days_in_month {
+            Date {
+                day: self.day + 1,
+                ..*self
+            }
+        } else if self.month < 12 {
+            Date {
+                year: self.year,
+                month: self.month + 1,
+                day: 1,
+            }
+        } else {
+            Date {
+                year: self.year + 1,
+                month: 1,
+                day: 1,
+            }
+        }
+    }
+
     /// The year (AD/CE), in the range `1..`
     #[inline]
     #[must_use]
@@ -0,0 +1,612 @@
+//! Detection of the compiler version, with extra information about how it was obtained.
+//!
+//! For most uses, [`crate::detect_version`] is sufficient.
+
+use crate::build::CompilerInfo;
+use crate::version::{Channel, CompilerKind, RustVersion, StableVersionSpec};
+use crate::VersionDetectionError;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// The most recent stable release known to this version of the crate.
+///
+/// Used by [`FallbackPolicy::AssumeLatestStable`].
+const LATEST_KNOWN_STABLE: RustVersion = RustVersion {
+    major: 1,
+    minor: 95,
+    patch: 0,
+    channel: Channel::Stable,
+};
+
+/// What to assume when the compiler version cannot be detected.
+///
+/// Used by [`detect_version_or`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FallbackPolicy {
+    /// Assume the most recent stable release known to this crate.
+    ///
+    /// This is updated with each release of the crate,
+    /// so it may become outdated if the crate is not updated.
+    AssumeLatestStable,
+    /// Assume the specified stable version, typically the minimum supported rust version.
+    ///
+    /// If the patch version is not specified, it is assumed to be zero.
+    AssumeMinimum(StableVersionSpec),
+    /// Do not assume anything, returning the detection error instead.
+    Fail,
+}
+
+/// A version that was either detected from the compiler or assumed as a fallback.
+///
+/// Returned by [`detect_version_or`] and [`detect_version_details`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DetectedVersion {
+    version: RustVersion,
+    compiler_kind: Option<CompilerKind>,
+    vendor_info: Option<String>,
+    confidence: Option<ParseConfidence>,
+    oddities: Vec<String>,
+    tool_context: ToolContext,
+    fallback: Option<FallbackPolicy>,
+}
+impl DetectedVersion {
+    fn from_info(info: CompilerInfo) -> DetectedVersion {
+        DetectedVersion {
+            version: info.version,
+            compiler_kind: Some(info.kind),
+            vendor_info: info.vendor_info,
+            confidence: Some(info.confidence),
+            oddities: info.oddities,
+            tool_context: info.tools.union(ToolContext::from_env()),
+            fallback: None,
+        }
+    }
+
+    /// The detected version, or the assumed version if detection failed.
+    #[inline]
+    #[must_use]
+    pub fn version(&self) -> RustVersion {
+        self.version
+    }
+
+    /// The kind of compiler which was detected, or `None` if the version was assumed.
+    ///
+    /// For an alternative compiler, the [version](Self::version) is the closest equivalent rustc version.
+    #[inline]
+    #[must_use]
+    pub fn compiler_kind(&self) -> Option<CompilerKind> {
+        self.compiler_kind
+    }
+
+    /// Extra annotations added to `rustc --version` by a vendor or distribution,
+    /// like `Fedora 1.75.0-1.fc39` or `gentoo`.
+    ///
+    /// Returns `None` for official builds, or if the version was assumed.
+    #[inline]
+    #[must_use]
+    pub fn vendor_info(&self) -> Option<&str> {
+        self.vendor_info.as_ref().map(String::as_str)
+    }
+
+    /// A best-effort classification of the [vendor info](Self::vendor_info).
+    ///
+    /// Returns `None` if there is no vendor info.
+    #[must_use]
+    pub fn vendor(&self) -> Option<Vendor> {
+        self.vendor_info().map(Vendor::classify)
+    }
+
+    /// How confident the parsing of the compiler output was, or `None` if the version was assumed.
+    ///
+    /// This is only [`ParseConfidence::BestEffort`] when using [`detect_version_lenient`].
+    #[inline]
+    #[must_use]
+    pub fn confidence(&self) -> Option<ParseConfidence> {
+        self.confidence
+    }
+
+    /// Descriptions of the unexpected parts of the compiler output,
+    /// which were skipped by [lenient parsing](detect_version_lenient).
+    #[inline]
+    #[must_use]
+    pub fn oddities(&self) -> &[String] {
+        &self.oddities
+    }
+
+    /// The tools that the build is running under, like clippy or miri.
+    ///
+    /// This combines the environment with what was noticed while running the compiler,
+    /// such as `clippy-driver` needing to be run again with `--rustc`.
+    /// It does not affect the detected version.
+    #[inline]
+    #[must_use]
+    pub fn tool_context(&self) -> ToolContext {
+        self.tool_context
+    }
+
+    /// Check if the version was assumed because detection failed.
+    ///
+    /// This distinguishes a guess from a real detection.
+    #[inline]
+    #[must_use]
+    pub fn is_fallback(&self) -> bool {
+        self.fallback.is_some()
+    }
+
+    /// The policy used to assume the version, or `None` if the version was actually detected.
+    #[inline]
+    #[must_use]
+    pub fn fallback(&self) -> Option<FallbackPolicy> {
+        self.fallback
+    }
+}
+
+/// Detect the current version, falling back to an assumed version if detection fails.
+///
+/// This is useful in environments where `rustc` cannot be executed,
+/// such as some IDE analyzers.
+/// If detection fails, a `cargo:warning` is emitted explaining the failure
+/// and the version which was assumed instead.
+///
+/// # Errors
+/// Returns the detection error only if the policy is [`FallbackPolicy::Fail`].
+///
+/// # Example
+/// ```
+/// # use rustversion_detect::{detect_version_or, FallbackPolicy, StableVersionSpec};
+/// let detected = detect_version_or(FallbackPolicy::AssumeMinimum(StableVersionSpec::minor(1, 31)))
+///     .unwrap();
+/// if detected.version().is_since_minor_version(1, 80) && !detected.is_fallback() {
+///     println!("cargo:rustc-cfg=fancy_feature");
+/// }
+/// ```
+pub fn detect_version_or(policy: FallbackPolicy) -> Result<DetectedVersion, VersionDetectionError> {
+    let result = detect_version_details();
+    if let Err(ref error) = result {
+        if let Some((version, reason)) = assumed_version(policy) {
+            crate::emit_warning(&format!("Failed to detect rustc version: {}", error));
+            crate::emit_warning(&format!("Assuming rustc {} ({})", version, reason));
+        }
+    }
+    apply_fallback(result, policy, ToolContext::from_env())
+}
+
+/// Apply the policy to the result of detection,
+/// substituting the assumed version if detection failed.
+fn apply_fallback(
+    result: Result<DetectedVersion, VersionDetectionError>,
+    policy: FallbackPolicy,
+    tool_context: ToolContext,
+) -> Result<DetectedVersion, VersionDetectionError> {
+    let error = match result {
+        Ok(detected) => return Ok(detected),
+        Err(error) => error,
+    };
+    let (version, _) = match assumed_version(policy) {
+        Some(assumed) => assumed,
+        None => return Err(error),
+    };
+    Ok(DetectedVersion {
+        version,
+        compiler_kind: None,
+        vendor_info: None,
+        confidence: None,
+        oddities: Vec::new(),
+        tool_context,
+        fallback: Some(policy),
+    })
+}
+
+/// The version assumed by the policy, along with an explanation for the warning.
+///
+/// Returns `None` for [`FallbackPolicy::Fail`].
+fn assumed_version(policy: FallbackPolicy) -> Option<(RustVersion, &'static str)> {
+    match policy {
+        FallbackPolicy::AssumeLatestStable => Some((
+            LATEST_KNOWN_STABLE,
+            "the latest stable release known to rustversion-detect",
+        )),
+        FallbackPolicy::AssumeMinimum(spec) => {
+            Some((spec.to_version(), "the specified minimum version"))
+        }
+        FallbackPolicy::Fail => None,
+    }
+}
+
+/// Detect the current version along with the kind of compiler.
+///
+/// Alternative compilers like gccrs and mrustc are recognized,
+/// and assigned the closest equivalent rustc version.
+/// Builds of rustc using an alternative codegen backend are recognized
+/// from the `-Zcodegen-backend` flag in `CARGO_ENCODED_RUSTFLAGS`.
+///
+/// The result shares a cache with [`crate::detect_version`].
+///
+/// # Errors
+/// Returns an error if unable to execute the compiler or parse its output.
+///
+/// # Example
+/// ```no_run
+/// # use rustversion_detect::{detect::detect_version_details, CompilerKind};
+/// let detected = detect_version_details().unwrap();
+/// if detected.compiler_kind() == Some(CompilerKind::Gccrs) {
+///     println!("cargo:rustc-cfg=gccrs");
+/// }
+/// ```
+pub fn detect_version_details() -> Result<DetectedVersion, VersionDetectionError> {
+    let info = crate::detect_cached(&crate::build::Options::default())?;
+    Ok(DetectedVersion::from_info(info))
+}
+
+/// Detect the current version, recovering a best-effort version from unexpected compiler output.
+///
+/// Strict parsing is attempted first.
+/// If the output is not recognized, every line is scanned for a version,
+/// and an unknown channel suffix (like `1.80.0-custom`) is treated as a development build.
+/// The [confidence](DetectedVersion::confidence) is then [`ParseConfidence::BestEffort`],
+/// with the skipped [oddities](DetectedVersion::oddities) recorded.
+///
+/// A best-effort result is not cached, so it will never be returned by [`crate::detect_version`].
+///
+/// # Errors
+/// Returns an error if unable to execute the compiler,
+/// or if no version can be found in its output.
+///
+/// # Example
+/// ```no_run
+/// # use rustversion_detect::{detect::detect_version_lenient, ParseConfidence};
+/// let detected = detect_version_lenient().unwrap();
+/// if detected.confidence() == Some(ParseConfidence::BestEffort) {
+///     for oddity in detected.oddities() {
+///         println!("cargo:warning=Unexpected rustc version output: {}", oddity);
+///     }
+/// }
+/// ```
+pub fn detect_version_lenient() -> Result<DetectedVersion, VersionDetectionError> {
+    let info = crate::detect_cached(&crate::build::Options {
+        lenient: true,
+        ..crate::build::Options::default()
+    })?;
+    Ok(DetectedVersion::from_info(info))
+}
+
+/// How confident the parsing of the compiler output was.
+///
+/// See [`detect_version_lenient`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ParseConfidence {
+    /// The output was in the expected format.
+    Exact,
+    /// The output was not in the expected format,
+    /// so the version was recovered on a best-effort basis.
+    BestEffort,
+}
+
+/// The vendor or distribution which built the compiler.
+///
+/// See [`DetectedVersion::vendor`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Vendor {
+    /// The qualified [Ferrocene](https://ferrocene.dev) toolchain.
+    Ferrocene,
+    /// The Fedora package.
+    Fedora,
+    /// The Red Hat Enterprise Linux package.
+    RedHat,
+    /// The Gentoo package.
+    Gentoo,
+    /// The Arch Linux package.
+    ArchLinux,
+    /// The Alpine Linux package.
+    Alpine,
+    /// The Homebrew package.
+    Homebrew,
+    /// A vendor which is not recognized.
+    Other,
+    #[doc(hidden)]
+    __NonExhaustive,
+}
+impl Vendor {
+    /// Classify the vendor info from `rustc --version`.
+    ///
+    /// This is a best-effort guess based on well-known names,
+    /// falling back to [`Vendor::Other`].
+    #[must_use]
+    pub fn classify(vendor_info: &str) -> Vendor {
+        let info = vendor_info.to_ascii_lowercase();
+        let known = &[
+            ("ferrocene", Vendor::Ferrocene),
+            ("fedora", Vendor::Fedora),
+            ("red hat", Vendor::RedHat),
+            ("gentoo", Vendor::Gentoo),
+            ("arch linux", Vendor::ArchLinux),
+            ("alpine", Vendor::Alpine),
+            ("homebrew", Vendor::Homebrew),
+        ];
+        known
+            .iter()
+            .find(|&&(name, _)| info.contains(name))
+            .map_or(Vendor::Other, |&(_, vendor)| vendor)
+    }
+}
+
+/// Tell cargo to rerun the build script if any input to version detection changes.
+///
+/// This emits a `cargo:rerun-if-env-changed` directive for every environment variable
+/// that affects the detection results, including `RUSTC`, `RUSTC_WRAPPER`,
+/// `RUSTUP_TOOLCHAIN`, `RUSTC_BOOTSTRAP` and `RUSTFLAGS`.
+/// Without these, switching toolchains with `cargo +nightly` can leave stale results
+/// when the build script uses `cargo:rerun-if-changed=build.rs`.
+///
+/// See [`emit_compiler_rerun_directive`] to also rerun when the compiler binary changes,
+/// and [`emit_tool_context_rerun_directives`] to rerun when the [`ToolContext`] changes.
+pub fn emit_rerun_directives() {
+    for directive in env_rerun_directives(crate::build::ENV_VARS) {
+        println!("{}", directive);
+    }
+}
+
+/// Environment variables which only affect the [`ToolContext`].
+const TOOL_ENV_VARS: &[&str] = &[
+    "RUSTC_WORKSPACE_WRAPPER",
+    "CLIPPY_ARGS",
+    "MIRI_SYSROOT",
+    "RA_RUSTC_WRAPPER",
+];
+
+/// Tell cargo to rerun the build script if the tools the build is running under change.
+///
+/// This emits a `cargo:rerun-if-env-changed` directive for the variables read by
+/// [`ToolContext::from_env`] which [`emit_rerun_directives`] does not already cover.
+/// Only use this if the output of the build script depends on the [`ToolContext`],
+/// since switching between `cargo build`, `cargo clippy` and rust-analyzer
+/// will then rerun the build script every time.
+pub fn emit_tool_context_rerun_directives() {
+    for directive in env_rerun_directives(TOOL_ENV_VARS) {
+        println!("{}", directive);
+    }
+}
+
+fn env_rerun_directives(vars: &[&str]) -> Vec<String> {
+    vars.iter()
+        .map(|var| format!("cargo:rerun-if-env-changed={}", var))
+        .collect()
+}
+
+/// Tell cargo to rerun the build script if the compiler binary changes.
+///
+/// The compiler is resolved from the `RUSTC` environment variable,
+/// searching `PATH` if necessary.
+/// Does nothing if the compiler binary cannot be found.
+///
+/// When using `rustup`, the binary is usually a proxy which does not change
+/// when switching toolchains, so this should be combined with [`emit_rerun_directives`].
+pub fn emit_compiler_rerun_directive() {
+    let path = crate::build::Rustc::from_env().resolve_path();
+    if let Some(directive) = compiler_rerun_directive(path.as_ref().map(PathBuf::as_path)) {
+        println!("{}", directive);
+    }
+}
+
+fn compiler_rerun_directive(path: Option<&Path>) -> Option<String> {
+    path.map(|path| format!("cargo:rerun-if-changed={}", path.display()))
+}
+
+/// The tools that the build is running under.
+///
+/// Build scripts can use this to skip expensive work that is not needed for analysis,
+/// like compiling C code for clippy or rust-analyzer.
+/// Several tools may be detected at once.
+///
+/// See [`DetectedVersion::tool_context`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[allow(clippy::struct_excessive_bools)] // the tools are independent
+pub struct ToolContext {
+    pub(crate) clippy: bool,
+    pub(crate) miri: bool,
+    pub(crate) rust_analyzer: bool,
+    pub(crate) mirai: bool,
+}
+impl ToolContext {
+    /// Determine the tool context from environment variables alone, without running the compiler.
+    ///
+    /// This recognizes:
+    /// - clippy, from a `clippy-driver` wrapper or the `CLIPPY_ARGS` variable set by `cargo clippy`
+    /// - miri, from the `MIRI_SYSROOT` or `CARGO_CFG_MIRI` variables set by `cargo miri`
+    /// - rust-analyzer, from the `RA_RUSTC_WRAPPER` variable
+    /// - mirai, from a `mirai` wrapper
+    #[must_use]
+    pub fn from_env() -> ToolContext {
+        ToolContext::from_vars(|name| env::var_os(name))
+    }
+
+    fn from_vars<F: Fn(&str) -> Option<OsString>>(var: F) -> ToolContext {
+        let has_wrapper = |name: &str| {
+            ["RUSTC_WRAPPER", "RUSTC_WORKSPACE_WRAPPER"]
+                .iter()
+                .any(|key| {
+                    var(key).map_or(false, |wrapper| {
+                        Path::new(&wrapper)
+                            .file_stem()
+                            .map_or(false, |stem| stem == name)
+                    })
+                })
+        };
+        ToolContext {
+            clippy: var("CLIPPY_ARGS").is_some() || has_wrapper("clippy-driver"),
+            miri: var("MIRI_SYSROOT").is_some() || var("CARGO_CFG_MIRI").is_some(),
+            rust_analyzer: var("RA_RUSTC_WRAPPER").is_some(),
+            mirai: has_wrapper("mirai"),
+        }
+    }
+
+    /// Combine the tools detected by two sources.
+    pub(crate) fn union(self, other: ToolContext) -> ToolContext {
+        ToolContext {
+            clippy: self.clippy || other.clippy,
+            miri: self.miri || other.miri,
+            rust_analyzer: self.rust_analyzer || other.rust_analyzer,
+            mirai: self.mirai || other.mirai,
+        }
+    }
+
+    /// Check if the crate is being checked by clippy.
+    #[inline]
+    #[must_use]
+    pub fn is_clippy(&self) -> bool {
+        self.clippy
+    }
+
+    /// Check if the crate is being interpreted by miri.
+    #[inline]
+    #[must_use]
+    pub fn is_miri(&self) -> bool {
+        self.miri
+    }
+
+    /// Check if the build was started by rust-analyzer.
+    #[inline]
+    #[must_use]
+    pub fn is_rust_analyzer(&self) -> bool {
+        self.rust_analyzer
+    }
+
+    /// Check if the crate is being analyzed by the [mirai] abstract interpreter.
+    ///
+    /// [mirai]: https://github.com/facebookexperimental/MIRAI
+    #[inline]
+    #[must_use]
+    pub fn is_mirai(&self) -> bool {
+        self.mirai
+    }
+
+    /// Check if the build only checks the crate, without producing a usable binary.
+    ///
+    /// This is the case for clippy and rust-analyzer.
+    /// Cargo does not tell build scripts about a plain `cargo check`, so it is not detected.
+    #[inline]
+    #[must_use]
+    pub fn is_check_only(&self) -> bool {
+        self.clippy || self.rust_analyzer
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        apply_fallback, compiler_rerun_directive, env_rerun_directives, DetectedVersion,
+        FallbackPolicy, ToolContext, Vendor, LATEST_KNOWN_STABLE, TOOL_ENV_VARS,
+    };
+    use crate::{RustVersion, StableVersionSpec, VersionDetectionError};
+    use std::ffi::OsString;
+    use std::path::Path;
+
+    fn failed() -> Result<DetectedVersion, VersionDetectionError> {
+        Err(VersionDetectionError::new("Error: rustc not found".into()))
+    }
+
+    #[test]
+    fn fallback_policy() {
+        let latest = apply_fallback(
+            failed(),
+            FallbackPolicy::AssumeLatestStable,
+            ToolContext::default(),
+        )
+        .unwrap();
+        assert_eq!(latest.version(), LATEST_KNOWN_STABLE);
+        assert!(latest.is_fallback());
+        assert_eq!(latest.fallback(), Some(FallbackPolicy::AssumeLatestStable));
+        assert_eq!(latest.compiler_kind(), None);
+
+        let minimum = FallbackPolicy::AssumeMinimum(StableVersionSpec::minor(1, 31));
+        let assumed = apply_fallback(failed(), minimum, ToolContext::default()).unwrap();
+        assert_eq!(assumed.version(), RustVersion::stable(1, 31, 0));
+        assert_eq!(assumed.fallback(), Some(minimum));
+
+        assert!(apply_fallback(failed(), FallbackPolicy::Fail, ToolContext::default()).is_err());
+    }
+
+    #[test]
+    fn fallback_policy_unused() {
+        let detected = DetectedVersion {
+            version: RustVersion::stable(1, 80, 1),
+            compiler_kind: None,
+            vendor_info: None,
+            confidence: None,
+            oddities: Vec::new(),
+            tool_context: ToolContext::default(),
+            fallback: None,
+        };
+        for &policy in &[
+            FallbackPolicy::AssumeLatestStable,
+            FallbackPolicy::AssumeMinimum(StableVersionSpec::minor(1, 31)),
+            FallbackPolicy::Fail,
+        ] {
+            let result =
+                apply_fallback(Ok(detected.clone()), policy, ToolContext::default()).unwrap();
+            assert_eq!(result.version(), RustVersion::stable(1, 80, 1));
+            assert!(!result.is_fallback());
+        }
+    }
+
+    #[test]
+    fn tool_context() {
+        let context = |vars: &[(&str, &str)]| {
+            ToolContext::from_vars(|name| {
+                vars.iter()
+                    .find(|&&(key, _)| key == name)
+                    .map(|&(_, value)| OsString::from(value))
+            })
+        };
+        assert_eq!(context(&[]), ToolContext::default());
+        let clippy = context(&[(
+            "RUSTC_WORKSPACE_WRAPPER",
+            "/home/user/.rustup/toolchains/stable/bin/clippy-driver",
+        )]);
+        assert!(clippy.is_clippy() && clippy.is_check_only() && !clippy.is_miri());
+        assert!(context(&[("CARGO_CFG_MIRI", "")]).is_miri());
+        let rust_analyzer = context(&[("RA_RUSTC_WRAPPER", "1")]);
+        assert!(rust_analyzer.is_rust_analyzer() && rust_analyzer.is_check_only());
+        assert!(context(&[("RUSTC_WRAPPER", "mirai")]).is_mirai());
+        assert!(!context(&[("RUSTC_WRAPPER", "sccache")]).is_mirai());
+    }
+
+    #[test]
+    fn rerun_directives() {
+        let directives = env_rerun_directives(crate::build::ENV_VARS);
+        assert_eq!(directives.len(), crate::build::ENV_VARS.len());
+        for (directive, var) in directives.iter().zip(crate::build::ENV_VARS) {
+            assert_eq!(*directive, format!("cargo:rerun-if-env-changed={}", var));
+        }
+        assert!(directives.contains(&"cargo:rerun-if-env-changed=RUSTUP_TOOLCHAIN".to_owned()));
+        assert!(!directives.contains(&"cargo:rerun-if-env-changed=CLIPPY_ARGS".to_owned()));
+        assert_eq!(
+            env_rerun_directives(TOOL_ENV_VARS)[..2],
+            [
+                "cargo:rerun-if-env-changed=RUSTC_WORKSPACE_WRAPPER",
+                "cargo:rerun-if-env-changed=CLIPPY_ARGS"
+            ]
+        );
+        assert_eq!(
+            compiler_rerun_directive(Some(Path::new("/usr/bin/rustc"))),
+            Some("cargo:rerun-if-changed=/usr/bin/rustc".to_owned())
+        );
+        assert_eq!(compiler_rerun_directive(None), None);
+    }
+
+    #[test]
+    fn classify_vendor() {
+        assert_eq!(Vendor::classify("Fedora 1.75.0-1.fc39"), Vendor::Fedora);
+        assert_eq!(Vendor::classify("Red Hat 1.75.0-1.el9"), Vendor::RedHat);
+        assert_eq!(Vendor::classify("gentoo"), Vendor::Gentoo);
+        assert_eq!(
+            Vendor::classify("Ferrocene by Ferrous Systems"),
+            Vendor::Ferrocene
+        );
+        assert_eq!(
+            Vendor::classify("built from a source tarball"),
+            Vendor::Other
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Configures a cfg for documentation builds on [docs.rs](https://docs.rs).
+//!
+//! Crates commonly use a `docsrs` cfg to enable `#![feature(doc_cfg)]`,
+//! which shows the features required by each item.
+//! This is only usable with a nightly toolchain,
+//! so the cfg should only be enabled when the feature gate is actually available.
+//!
+//! # Example
+//! ```no_run
+//! // in build.rs
+//! rustversion_detect::configure_docsrs();
+//! ```
+//!
+//! Then in `lib.rs`:
+//! ```
+//! #![cfg_attr(docsrs, feature(doc_cfg))]
+//! ```
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+
+use crate::probe::Prober;
+use crate::unstable::UnstableEnv;
+use crate::RustVersion;
+
+/// The feature gate which the cfg is intended to enable.
+const FEATURE: &str = "doc_cfg";
+
+/// Emit the `docsrs` cfg when building on docs.rs and `#![feature(doc_cfg)]` is usable.
+///
+/// Equivalent to `configure_docsrs_with_cfg("docsrs")`.
+/// See [`configure_docsrs_with_cfg`] for details.
+#[allow(clippy::must_use_candidate)] // the directives are useful even if the result is ignored
+pub fn configure_docsrs() -> bool {
+    configure_docsrs_with_cfg("docsrs")
+}
+
+/// Emit the specified cfg when building on docs.rs and `#![feature(doc_cfg)]` is usable.
+///
+/// The docs.rs environment is detected using the `DOCS_RS` environment variable.
+/// The feature must be usable by both rustdoc (see [`crate::detect_rustdoc_version`])
+/// and the compiler (checked with [`Prober::probe_feature_gate`]),
+/// since docs.rs compiles the crate with both.
+/// This respects `RUSTC_BOOTSTRAP` and `-Zallow-features`.
+///
+/// A `cargo:rustc-check-cfg` directive for the cfg is always emitted if cargo supports it,
+/// to avoid `unexpected_cfgs` warnings on other builds.
+///
+/// Returns whether the cfg was enabled.
+/// Failures to detect a version are reported as warnings and leave the cfg disabled.
+#[allow(clippy::must_use_candidate)] // the directives are useful even if the result is ignored
+pub fn configure_docsrs_with_cfg(cfg: &str) -> bool {
+    if supports_check_cfg() {
+        println!("cargo:rustc-check-cfg=cfg({})", cfg);
+    }
+    let enabled = is_doc_cfg_usable(env::var_os("DOCS_RS").as_ref().map(OsString::as_os_str));
+    if enabled {
+        println!("cargo:rustc-cfg={}", cfg);
+    }
+    enabled
+}
+
+/// Check if cargo accepts `cargo:rustc-check-cfg`, which was stabilized in 1.80.
+///
+/// Older versions warn about the directive.
+/// This falls back to the compiler version if the version of cargo cannot be detected,
+/// since cargo and rustc are usually the same version.
+fn supports_check_cfg() -> bool {
+    crate::detect_cargo_version()
+        .or_else(|_| crate::detect_version())
+        .map(|version| version.is_since_minor_version(1, 80))
+        .unwrap_or(false)
+}
+
+fn is_doc_cfg_usable(docs_rs: Option<&OsStr>) -> bool {
+    // avoid running rustdoc outside of docs.rs
+    if docs_rs.is_none() {
+        return false;
+    }
+    let rustdoc = match crate::detect_rustdoc_version() {
+        Ok(rustdoc) => rustdoc,
+        Err(e) => {
+            crate::emit_warning(&format!("Failed to detect rustdoc version: {}", e));
+            return false;
+        }
+    };
+    if !allows_doc_cfg(docs_rs, &rustdoc, &UnstableEnv::from_env()) {
+        return false;
+    }
+    let result = Prober::new().and_then(|prober| prober.probe_feature_gate(FEATURE, ""));
+    match result {
+        Ok(status) => status.is_available(),
+        Err(e) => {
+            crate::emit_warning(&format!("Failed to probe feature `{}`: {}", FEATURE, e));
+            false
+        }
+    }
+}
+
+/// Check if the cfg should be enabled, before probing the compiler.
+///
+/// This requires the `DOCS_RS` variable and a rustdoc which permits the feature.
+fn allows_doc_cfg(docs_rs: Option<&OsStr>, rustdoc: &RustVersion, unstable: &UnstableEnv) -> bool {
+    docs_rs.is_some()
+        && unstable
+            .effective_channel(&rustdoc.channel)
+            .allows_unstable_features()
+        && unstable.is_feature_allowed(FEATURE)
+}
+
+#[cfg(test)]
+mod test {
+    use super::allows_doc_cfg;
+    use crate::unstable::UnstableEnv;
+    use crate::{Channel, Date, RustVersion};
+    use std::ffi::OsStr;
+
+    #[test]
+    fn allows_doc_cfg_on_docsrs() {
+        let nightly = RustVersion {
+            major: 1,
+            minor: 97,
+            patch: 0,
+            channel: Channel::Nightly {
+                date: Date::new(2026, 5, 19),
+            },
+        };
+        let stable = RustVersion::stable(1, 95, 0);
+        let docs_rs = Some(OsStr::new("1"));
+        let default = UnstableEnv::default();
+        assert!(allows_doc_cfg(docs_rs, &nightly, &default));
+        assert!(!allows_doc_cfg(None, &nightly, &default));
+        assert!(!allows_doc_cfg(docs_rs, &stable, &default));
+        let bootstrap = UnstableEnv {
+            rustc_bootstrap: Some("1".into()),
+            ..UnstableEnv::default()
+        };
+        assert!(allows_doc_cfg(docs_rs, &stable, &bootstrap));
+        let restricted = UnstableEnv {
+            allowed_features: Some(vec!["never_type".into()]),
+            ..UnstableEnv::default()
+        };
+        assert!(!allows_doc_cfg(docs_rs, &nightly, &restricted));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // miri cannot spawn processes
+    fn outside_docsrs() {
+        if std::env::var_os("DOCS_RS").is_none() {
+            assert!(!super::configure_docsrs());
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! A typed snapshot of the environment cargo provides to build scripts.
+//!
+//! See <https://doc.rust-lang.org/cargo/reference/environment-variables.html#environment-variables-cargo-sets-for-build-scripts>
+//! for the meaning of each variable.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+
+use crate::build;
+use crate::target::TargetCfg;
+use crate::{RustVersion, VersionDetectionError};
+
+/// The detected compiler version,
+/// bundled with the environment variables cargo sets for build scripts.
+///
+/// # Example
+/// ```no_run
+/// // in build.rs
+/// let env = rustversion_detect::BuildEnv::from_env().unwrap();
+/// if env.target_os() == Some("linux") && !env.is_cross_compiling() {
+///     println!("cargo:rustc-cfg=native_linux");
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BuildEnv {
+    version: RustVersion,
+    target: String,
+    host: String,
+    profile: String,
+    opt_level: String,
+    debug: String,
+    target_cfg: TargetCfg,
+    rustflags: Vec<String>,
+}
+impl BuildEnv {
+    /// Detect the compiler version and read the build script environment of the current process.
+    ///
+    /// The version is detected by [`crate::detect_version`], sharing its cache.
+    ///
+    /// # Errors
+    /// Returns an error if unable to detect the version,
+    /// or if a variable cargo always sets for build scripts is missing or not valid UTF-8.
+    /// The same applies to the rustflags variables, which are optional.
+    pub fn from_env() -> Result<BuildEnv, VersionDetectionError> {
+        let version = crate::detect_version()?;
+        let mut vars = HashMap::new();
+        let mut invalid = HashSet::new();
+        for (key, value) in env::vars_os() {
+            // variables with names that are not valid UTF-8 are never read
+            if let Ok(key) = key.into_string() {
+                match value.into_string() {
+                    Ok(value) => {
+                        vars.insert(key, value);
+                    }
+                    Err(_) => {
+                        invalid.insert(key);
+                    }
+                }
+            }
+        }
+        BuildEnv::from_vars(version, &vars, &invalid)
+    }
+
+    /// Create a snapshot from the specified version and environment variables,
+    /// instead of the environment of the current process.
+    ///
+    /// This is useful for testing build script logic.
+    ///
+    /// # Errors
+    /// Returns an error if a variable cargo always sets for build scripts is missing.
+    ///
+    /// # Example
+    /// ```
+    /// # use rustversion_detect::{BuildEnv, RustVersion};
+    /// # use std::collections::{HashMap, HashSet};
+    /// let mut vars = HashMap::new();
+    /// vars.insert("TARGET".to_owned(), "aarch64-unknown-linux-gnu".to_owned());
+    /// vars.insert("HOST".to_owned(), "x86_64-unknown-linux-gnu".to_owned());
+    /// vars.insert("PROFILE".to_owned(), "release".to_owned());
+    /// vars.insert("OPT_LEVEL".to_owned(), "3".to_owned());
+    /// vars.insert("DEBUG".to_owned(), "false".to_owned());
+    /// vars.insert("CARGO_CFG_TARGET_OS".to_owned(), "linux".to_owned());
+    /// let env = BuildEnv::from_env_map(RustVersion::stable(1, 80, 0), &vars).unwrap();
+    /// assert!(env.is_cross_compiling());
+    /// assert_eq!(env.target_os(), Some("linux"));
+    /// ```
+    pub fn from_env_map(
+        version: RustVersion,
+        vars: &HashMap<String, String>,
+    ) -> Result<BuildEnv, VersionDetectionError> {
+        BuildEnv::from_vars(version, vars, &HashSet::new())
+    }
+
+    /// Create a snapshot from the variables,
+    /// given the names of the variables whose values are not valid UTF-8.
+    fn from_vars(
+        version: RustVersion,
+        vars: &HashMap<String, String>,
+        invalid: &HashSet<String>,
+    ) -> Result<BuildEnv, VersionDetectionError> {
+        let get = |key: &str| -> Result<Option<&str>, VersionDetectionError> {
+            if invalid.contains(key) {
+                return Err(VersionDetectionError::new(format!(
+                    "Error: environment variable `{}` is not valid UTF-8",
+                    key
+                )));
+            }
+            Ok(vars.get(key).map(String::as_str))
+        };
+        let require = |key: &str| -> Result<String, VersionDetectionError> {
+            get(key)?.map(String::from).ok_or_else(|| {
+                VersionDetectionError::new(format!(
+                    "Error: missing environment variable `{}`, which cargo sets for build scripts",
+                    key
+                ))
+            })
+        };
+        Ok(BuildEnv {
+            version,
+            target: require("TARGET")?,
+            host: require("HOST")?,
+            profile: require("PROFILE")?,
+            opt_level: require("OPT_LEVEL")?,
+            debug: require("DEBUG")?,
+            target_cfg: TargetCfg::from_cargo_vars(
+                vars.iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str())),
+            ),
+            rustflags: build::parse_rustflags(get("CARGO_ENCODED_RUSTFLAGS")?, get("RUSTFLAGS")?),
+        })
+    }
+
+    /// The detected compiler version.
+    #[inline]
+    #[must_use]
+    pub fn version(&self) -> &RustVersion {
+        &self.version
+    }
+
+    /// The target triple being compiled for, from `TARGET`.
+    #[inline]
+    #[must_use]
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// The triple of the host running the build, from `HOST`.
+    #[inline]
+    #[must_use]
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Check if the target differs from the host.
+    #[inline]
+    #[must_use]
+    pub fn is_cross_compiling(&self) -> bool {
+        self.target != self.host
+    }
+
+    /// The base profile, from `PROFILE`.
+    ///
+    /// This is either `debug` or `release`, even for custom profiles.
+    #[inline]
+    #[must_use]
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// Check if the profile is based on `release`.
+    #[inline]
+    #[must_use]
+    pub fn is_release(&self) -> bool {
+        self.profile == "release"
+    }
+
+    /// The optimization level, from `OPT_LEVEL`.
+    ///
+    /// This is `0` through `3`, or `s` and `z` to optimize for size.
+    #[inline]
+    #[must_use]
+    pub fn opt_level(&self) -> &str {
+        &self.opt_level
+    }
+
+    /// The amount of debug information, from `DEBUG`.
+    ///
+    /// This is `true` or `false` in older versions of cargo,
+    /// and a level like `line-tables-only` in newer versions.
+    #[inline]
+    #[must_use]
+    pub fn debug(&self) -> &str {
+        &self.debug
+    }
+
+    /// Check if any debug information is generated.
+    #[must_use]
+    pub fn has_debug_info(&self) -> bool {
+        match &*self.debug {
+            "false" | "0" | "none" => false,
+            _ => true,
+        }
+    }
+
+    /// The cfgs of the target, from the `CARGO_CFG_*` variables.
+    #[inline]
+    #[must_use]
+    pub fn target_cfg(&self) -> &TargetCfg {
+        &self.target_cfg
+    }
+
+    /// The operating system of the target, like `linux`.
+    #[inline]
+    #[must_use]
+    pub fn target_os(&self) -> Option<&str> {
+        self.target_cfg.target_os()
+    }
+
+    /// The architecture of the target, like `x86_64`.
+    #[inline]
+    #[must_use]
+    pub fn target_arch(&self) -> Option<&str> {
+        self.target_cfg.target_arch()
+    }
+
+    /// The environment (ABI) of the target, like `gnu` or `msvc`.
+    #[inline]
+    #[must_use]
+    pub fn target_env(&self) -> Option<&str> {
+        self.target_cfg.target_env()
+    }
+
+    /// The flags passed to the compiler,
+    /// from `CARGO_ENCODED_RUSTFLAGS` or falling back to `RUSTFLAGS`.
+    #[inline]
+    #[must_use]
+    pub fn rustflags(&self) -> &[String] {
+        &self.rustflags
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BuildEnv;
+    use crate::RustVersion;
+    use std::collections::{HashMap, HashSet};
+
+    fn vars(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn from_env_map() {
+        let vars = vars(&[
+            ("TARGET", "x86_64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+            ("PROFILE", "debug"),
+            ("OPT_LEVEL", "0"),
+            ("DEBUG", "true"),
+            ("CARGO_CFG_TARGET_OS", "linux"),
+            ("CARGO_CFG_TARGET_ARCH", "x86_64"),
+            ("CARGO_CFG_TARGET_ABI", ""),
+            ("CARGO_CFG_TARGET_FEATURE", "fxsr,sse,sse2"),
+            ("CARGO_CFG_UNIX", ""),
+            ("CARGO_ENCODED_RUSTFLAGS", "-C\x1ftarget-cpu=native"),
+            ("RUSTFLAGS", "--cfg ignored"),
+        ]);
+        let env = BuildEnv::from_env_map(RustVersion::stable(1, 80, 0), &vars).unwrap();
+        assert_eq!(env.version(), &RustVersion::stable(1, 80, 0));
+        assert!(!env.is_cross_compiling());
+        assert!(!env.is_release());
+        assert!(env.has_debug_info());
+        assert_eq!(env.target_os(), Some("linux"));
+        assert_eq!(env.target_arch(), Some("x86_64"));
+        assert_eq!(env.target_env(), None);
+        assert_eq!(env.target_cfg().get("target_abi"), Some(""));
+        assert!(env.target_cfg().has_flag("unix"));
+        assert!(env.target_cfg().target_feature("sse2"));
+        assert_eq!(env.rustflags(), &["-C", "target-cpu=native"]);
+    }
+
+    #[test]
+    fn missing_var() {
+        let vars = vars(&[("TARGET", "x86_64-unknown-linux-gnu")]);
+        let error = BuildEnv::from_env_map(RustVersion::stable(1, 80, 0), &vars).unwrap_err();
+        assert!(error.to_string().contains("missing"), "{}", error);
+    }
+
+    #[test]
+    fn invalid_var() {
+        let vars = vars(&[("TARGET", "x86_64-unknown-linux-gnu")]);
+        let invalid = ["HOST".to_owned()].iter().cloned().collect::<HashSet<_>>();
+        let error =
+            BuildEnv::from_vars(RustVersion::stable(1, 80, 0), &vars, &invalid).unwrap_err();
+        assert!(
+            error.to_string().contains("`HOST` is not valid UTF-8"),
+            "{}",
+            error
+        );
+    }
+}
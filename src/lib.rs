@@ -41,18 +41,50 @@
 //!     }
 //! }
 //! ```
+//!
+//! If `rustc` cannot be executed, such as in some IDE analyzers,
+//! the call to `unwrap()` will panic.
+//! Use [`detect_version_or`] to assume a [fallback version](FallbackPolicy) instead.
 // These lints indicate serious problems which I would normally mark as #[deny(...)].
 // However, failing the build could cause problems for users of this library.
 #![warn(missing_docs)]
 use std::error::Error;
 use std::fmt::{self, Display};
+use std::path::Path;
 
 mod build;
+pub mod cargo;
+pub mod clippy;
 pub mod date;
+pub mod detect;
+pub mod docsrs;
+pub mod env;
+pub mod msrv;
+pub mod probe;
+pub mod report;
+pub mod sysroot;
+pub mod target;
+mod toml;
+pub mod toolchain;
+mod unstable;
 pub mod version;
 
+pub use crate::cargo::detect_cargo_version;
 pub use crate::date::Date;
-pub use crate::version::{Channel, RustVersion, StableVersionSpec};
+pub use crate::detect::{
+    detect_version_details, detect_version_or, emit_compiler_rerun_directive,
+    emit_rerun_directives, emit_tool_context_rerun_directives, DetectedVersion, FallbackPolicy,
+    ParseConfidence, ToolContext, Vendor,
+};
+pub use crate::docsrs::configure_docsrs;
+pub use crate::env::BuildEnv;
+pub use crate::msrv::{require_msrv_from_manifest, require_since};
+pub use crate::report::{detect_version_with_report, DetectionReport};
+pub use crate::sysroot::{
+    detect_sysroot, detect_target_libdir, has_rust_src, has_target_installed,
+};
+pub use crate::target::TargetCfg;
+pub use crate::version::{Channel, CompilerKind, EffectiveChannel, RustVersion, StableVersionSpec};
 
 /// Detect the current version by executing `rustc`.
 ///
@@ -67,23 +99,111 @@ pub use crate::version::{Channel, RustVersion, StableVersionSpec};
 /// # Errors
 /// Returns an error if unable to execute the result compiler
 /// or unable to parse the result.
+/// See [`detect_version_or`] to assume a fallback version instead.
 pub fn detect_version() -> Result<crate::RustVersion, VersionDetectionError> {
-    {
-        let lock = state::state_mutex()
-            .read()
-            .unwrap_or_else(std::sync::PoisonError::into_inner);
-        if let Some(cached) = &*lock {
-            return Ok(*cached);
-        }
-        // release the lock & fallthrough to detection
+    detect_cached(&build::Options::default()).map(|info| info.version)
+}
+
+/// Detect the current version, reading rustup metadata instead of executing `rustc` if possible.
+///
+/// When `RUSTUP_TOOLCHAIN` is set, the toolchain directory contains a manifest
+/// specifying the exact version, so there is no need to spawn `rustc` through the rustup proxy.
+/// This falls back to the behavior of [`detect_version`] if a `RUSTC_WRAPPER` is set,
+/// if `RUSTC` does not refer to the active toolchain,
+/// or if anything about the metadata is unexpected.
+///
+/// The result shares a cache with [`detect_version`].
+///
+/// # Errors
+/// Returns an error if unable to execute the result compiler
+/// or unable to parse the result.
+pub fn detect_version_prefer_rustup() -> Result<crate::RustVersion, VersionDetectionError> {
+    detect_cached(&build::Options {
+        rustup_fast_path: true,
+        ..build::Options::default()
+    })
+    .map(|info| info.version)
+}
+
+/// Detect the version of the compiler that cargo would use when building in the specified directory.
+///
+/// Outside of a build script, `RUSTC` is usually not set,
+/// so [`detect_version`] falls back to executing plain `rustc`.
+/// This instead resolves the compiler the same way cargo does,
+/// honoring the `build.rustc`, `build.rustc-wrapper` and `build.rustc-workspace-wrapper` settings
+/// in `.cargo/config.toml` (searching upwards from `dir`, then in `CARGO_HOME`)
+/// and the corresponding `CARGO_BUILD_RUSTC*` environment variables.
+/// The `RUSTC`, `RUSTC_WRAPPER` and `RUSTC_WORKSPACE_WRAPPER` environment variables take precedence.
+///
+/// The compiler is executed in `dir`, so that rustup respects any toolchain override for the directory.
+/// This is useful for `xtask` binaries and tests.
+///
+/// Unlike [`detect_version`], the result is not cached.
+///
+/// # Example
+/// ```no_run
+/// let version = rustversion_detect::detect_version_in(std::path::Path::new(".")).unwrap();
+/// println!("Building with {}", version);
+/// ```
+///
+/// # Errors
+/// Returns an error if unable to read the configuration files,
+/// unable to execute the resulting compiler,
+/// or unable to parse the result.
+pub fn detect_version_in(dir: &Path) -> Result<crate::RustVersion, VersionDetectionError> {
+    build::determine_version_in(dir)
+}
+
+/// Detect the version of rustdoc by executing `rustdoc --version`.
+///
+/// Documentation builds use the `RUSTDOC` environment variable,
+/// which can refer to a different toolchain than `RUSTC`.
+/// For example, docs.rs builds documentation with a pinned nightly.
+/// Features like `doc_cfg` depend on the version of rustdoc rather than the compiler.
+///
+/// If the `RUSTDOC_WRAPPER` environment variable is set,
+/// rustdoc is executed through the wrapper,
+/// similar to how `RUSTC_WRAPPER` is handled by [`detect_version`].
+///
+/// The result is cached separately from [`detect_version`].
+///
+/// # Errors
+/// Returns an error if unable to execute rustdoc
+/// or unable to parse the result.
+pub fn detect_rustdoc_version() -> Result<crate::RustVersion, VersionDetectionError> {
+    if let Some(cached) = *state::read(state::rustdoc_version_cache()) {
+        return Ok(cached);
+    }
+    let version = build::query_version(&build::Rustc::rustdoc_from_env(), None)?;
+    *state::write(state::rustdoc_version_cache()) = Some(version);
+    Ok(version)
+}
+
+/// Detect information about the compiler, using the cached result if present.
+pub(crate) fn detect_cached(
+    options: &build::Options,
+) -> Result<build::CompilerInfo, VersionDetectionError> {
+    if let Some(cached) = cached_compiler_info() {
+        return Ok(cached);
     }
-    match build::determine_version() {
+    determine_and_cache(options, &mut Vec::new())
+}
+
+pub(crate) fn cached_compiler_info() -> Option<build::CompilerInfo> {
+    state::read(state::state_mutex()).clone()
+}
+
+/// Determine the version without checking the cache, caching the result if successful.
+pub(crate) fn determine_and_cache(
+    options: &build::Options,
+    attempts: &mut Vec<report::Attempt>,
+) -> Result<build::CompilerInfo, VersionDetectionError> {
+    match build::determine_version(options, attempts) {
         Ok(success) => {
-            {
-                let mut lock = state::state_mutex()
-                    .write()
-                    .unwrap_or_else(std::sync::PoisonError::into_inner);
-                *lock = Some(success);
+            // a best-effort result should not be used by strict detection
+            if success.confidence == detect::ParseConfidence::Exact {
+                let mut lock = state::write(state::state_mutex());
+                *lock = Some(success.clone());
             }
             Ok(success)
         }
@@ -91,7 +211,17 @@ pub fn detect_version() -> Result<crate::RustVersion, VersionDetectionError> {
     }
 }
 
-/// Indicates failure to detect the compiler's rust version.
+/// Emit a `cargo:warning` directive for each line of the message.
+///
+/// A single directive cannot span multiple lines.
+pub(crate) fn emit_warning(message: &str) {
+    for line in message.lines().filter(|line| !line.trim().is_empty()) {
+        println!("cargo:warning={}", line);
+    }
+}
+
+/// Indicates failure to detect the compiler's rust version,
+/// or other information from the compiler.
 #[derive(Debug)]
 pub struct VersionDetectionError {
     desc: String,
@@ -124,29 +254,85 @@ impl Error for VersionDetectionError {
     }
 }
 
-/// Caches the detected rust version.
+/// Caches detected information for the lifetime of the process.
 #[allow(unused_imports)]
 mod state {
-    use std::sync::{Once, RwLock};
+    use std::collections::HashMap;
+    use std::sync::{Once, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-    #[allow(deprecated)] // Only available since 1.32
-    static CACHED_STATE_INIT: Once = std::sync::ONCE_INIT;
-    static mut CACHED_STATE: Option<RwLock<Option<crate::RustVersion>>> = None;
-
-    pub fn state_mutex() -> &'static RwLock<Option<crate::RustVersion>> {
-        CACHED_STATE_INIT.call_once(|| {
-            // SAFETY: Will only be called once
-            unsafe {
-                CACHED_STATE = Some(RwLock::new(None));
-            }
-        });
-        // SAFETY: After completion of `Once::call_once`,
-        // the mutex is fully initialized and not `None`
-        unsafe {
-            match CACHED_STATE {
-                Some(ref mutex) => mutex,
-                None => std::hint::unreachable_unchecked(),
+    /// Define a function returning a lazily initialized static lock,
+    /// which initially contains the default value.
+    ///
+    /// This is necessary because `RwLock::new` is not a `const fn` on our MSRV.
+    macro_rules! lazy_lock {
+        ($(#[$attr:meta])* $name:ident: $ty:ty) => {
+            $(#[$attr])*
+            pub fn $name() -> &'static RwLock<$ty> {
+                #[allow(deprecated)] // Only available since 1.32
+                static INIT: Once = std::sync::ONCE_INIT;
+                static mut VALUE: Option<RwLock<$ty>> = None;
+                INIT.call_once(|| {
+                    // SAFETY: Will only be called once
+                    unsafe {
+                        VALUE = Some(RwLock::new(Default::default()));
+                    }
+                });
+                // SAFETY: After completion of `Once::call_once`,
+                // the lock is fully initialized and not `None`
+                unsafe {
+                    match VALUE {
+                        Some(ref lock) => lock,
+                        None => std::hint::unreachable_unchecked(),
+                    }
+                }
             }
-        }
+        };
+    }
+
+    lazy_lock!(
+        /// The detected rust version, along with other information about the compiler.
+        state_mutex: Option<crate::build::CompilerInfo>
+    );
+    lazy_lock!(
+        /// The results of compiling probes, keyed by the compiler invocation and code.
+        ///
+        /// A failed compilation is represented by the error output.
+        probe_cache: HashMap<String, Result<(), String>>
+    );
+    lazy_lock!(
+        /// The configuration of each target, keyed by the target triple.
+        ///
+        /// The empty string is used for the host.
+        target_cfg_cache: HashMap<String, crate::target::TargetCfg>
+    );
+    lazy_lock!(
+        /// The sysroot of the compiler.
+        sysroot_cache: Option<std::path::PathBuf>
+    );
+    lazy_lock!(
+        /// The detected rustdoc version.
+        rustdoc_version_cache: Option<crate::RustVersion>
+    );
+    lazy_lock!(
+        /// The detected clippy version, or `None` inside if clippy is not in use.
+        clippy_version_cache: Option<Option<crate::clippy::ClippyVersion>>
+    );
+    lazy_lock!(
+        /// The detected cargo version.
+        cargo_version_cache: Option<crate::RustVersion>
+    );
+    lazy_lock!(
+        /// The detected cargo version, along with build information.
+        cargo_verbose_version_cache: Option<crate::cargo::VerboseVersion>
+    );
+
+    /// Acquire a read lock, ignoring poisoning.
+    pub fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+        lock.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Acquire a write lock, ignoring poisoning.
+    pub fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+        lock.write().unwrap_or_else(PoisonError::into_inner)
     }
 }
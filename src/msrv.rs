@@ -0,0 +1,210 @@
+//! Enforces a minimum supported rust version (MSRV) from a build script.
+//!
+//! Building a crate with a compiler that is too old usually results in a wall of confusing errors.
+//! Checking the version up front gives a single clear message instead.
+//!
+//! # Example
+//! ```no_run
+//! // in build.rs
+//! rustversion_detect::require_msrv_from_manifest();
+//! ```
+
+use std::env;
+use std::process;
+
+use crate::version::{RustVersion, StableVersionParseError, StableVersionSpec};
+
+/// What to do when the detected compiler does not satisfy a version requirement.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OnUnsatisfied {
+    /// Emit `cargo:warning` lines, then abort the build script with an error.
+    Abort,
+    /// Only emit `cargo:warning` lines, allowing the build to continue.
+    Warn,
+}
+
+/// Require that the compiler is at least the specified stable version,
+/// aborting the build script if it is not.
+///
+/// The `message` is shown to the user alongside the detected and required versions.
+/// The channel of the compiler is ignored, consistent with [`RustVersion::is_since_stable`].
+///
+/// If the version cannot be detected, a warning is emitted and the build continues.
+///
+/// See [`check_since`] for a version that can only warn.
+pub fn require_since(spec: StableVersionSpec, message: &str) {
+    check_since(spec, message, OnUnsatisfied::Abort);
+}
+
+/// Require that the compiler satisfies the `rust-version` declared in `Cargo.toml`,
+/// aborting the build script if it does not.
+///
+/// Cargo passes this to build scripts using the `CARGO_PKG_RUST_VERSION` environment variable.
+/// Does nothing if the package does not declare a `rust-version`.
+///
+/// See [`check_msrv_from_manifest`] for a version that can only warn.
+pub fn require_msrv_from_manifest() {
+    check_msrv_from_manifest(OnUnsatisfied::Abort);
+}
+
+/// Check that the compiler is at least the specified stable version.
+///
+/// Returns `true` if the requirement is known to be satisfied.
+/// Otherwise, `cargo:warning` lines are emitted and the `mode` determines
+/// whether the build script is aborted.
+///
+/// If the version cannot be detected, a warning is emitted and this returns `false`
+/// without aborting, regardless of the `mode`.
+#[allow(clippy::must_use_candidate)] // the warnings are useful even if the result is ignored
+pub fn check_since(spec: StableVersionSpec, message: &str, mode: OnUnsatisfied) -> bool {
+    let version = match crate::detect_version() {
+        Ok(version) => version,
+        Err(e) => {
+            crate::emit_warning(message);
+            crate::emit_warning(&format!(
+                "Unable to check for rustc {} or newer: {}",
+                spec, e
+            ));
+            return false;
+        }
+    };
+    if version.is_since_stable(spec) {
+        return true;
+    }
+    unsatisfied(&version, spec, message, mode);
+    false
+}
+
+/// Check that the compiler satisfies the `rust-version` declared in `Cargo.toml`.
+///
+/// Behaves like [`check_since`], using the `CARGO_PKG_RUST_VERSION` environment variable.
+/// Returns `true` if the package does not declare a `rust-version`.
+#[allow(clippy::must_use_candidate)] // the warnings are useful even if the result is ignored
+pub fn check_msrv_from_manifest(mode: OnUnsatisfied) -> bool {
+    let text = env::var("CARGO_PKG_RUST_VERSION").unwrap_or_default();
+    if text.trim().is_empty() {
+        // avoid detecting the version if there is nothing to check
+        return true;
+    }
+    let package = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "this package".into());
+    let version = match crate::detect_version() {
+        Ok(version) => version,
+        Err(e) => {
+            crate::emit_warning(&format!(
+                "Unable to check the `rust-version` of {}: {}",
+                package, e
+            ));
+            return false;
+        }
+    };
+    match check(&version, &text) {
+        Ok(()) => true,
+        Err(Unsatisfied::TooOld(spec)) => {
+            let message = format!(
+                "{} requires rustc {} or newer (the `rust-version` in Cargo.toml)",
+                package, spec
+            );
+            unsatisfied(&version, spec, &message, mode);
+            false
+        }
+        Err(Unsatisfied::Invalid(e)) => {
+            crate::emit_warning(&format!(
+                "Unable to parse `rust-version` {:?} for {}: {:?}",
+                text.trim(),
+                package,
+                e
+            ));
+            false
+        }
+    }
+}
+
+/// The reason a `rust-version` requirement is not satisfied.
+#[derive(Clone, Debug)]
+enum Unsatisfied {
+    /// The compiler is older than the required version.
+    TooOld(StableVersionSpec),
+    /// The requirement could not be parsed.
+    Invalid(StableVersionParseError),
+}
+
+/// Check the version against a `rust-version` requirement like `1.31`.
+///
+/// An empty requirement is always satisfied.
+fn check(version: &RustVersion, spec: &str) -> Result<(), Unsatisfied> {
+    let spec = spec.trim();
+    if spec.is_empty() {
+        return Ok(());
+    }
+    let spec = spec
+        .parse::<StableVersionSpec>()
+        .map_err(Unsatisfied::Invalid)?;
+    if version.is_since_stable(spec) {
+        Ok(())
+    } else {
+        Err(Unsatisfied::TooOld(spec))
+    }
+}
+
+fn unsatisfied(version: &RustVersion, spec: StableVersionSpec, message: &str, mode: OnUnsatisfied) {
+    crate::emit_warning(message);
+    crate::emit_warning(&format!(
+        "Detected rustc {}, but rustc {} or newer is required",
+        version, spec
+    ));
+    match mode {
+        OnUnsatisfied::Warn => {}
+        OnUnsatisfied::Abort => {
+            eprintln!("error: {}", message);
+            eprintln!("  detected: rustc {}", version);
+            eprintln!("  required: rustc {} or newer", spec);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check, Unsatisfied};
+    use crate::{Channel, Date, RustVersion, StableVersionSpec};
+
+    /// Simplify the result for comparison,
+    /// giving the required version if the compiler is too old or `None` if the spec is invalid.
+    fn outcome(version: &RustVersion, spec: &str) -> Result<(), Option<StableVersionSpec>> {
+        check(version, spec).map_err(|e| match e {
+            Unsatisfied::TooOld(spec) => Some(spec),
+            Unsatisfied::Invalid(_) => None,
+        })
+    }
+
+    #[test]
+    fn check_rust_version() {
+        let version = RustVersion::stable(1, 80, 1);
+        for spec in &["", "  \n", "1.80", " 1.80.1 ", "1.31"] {
+            assert_eq!(outcome(&version, spec), Ok(()), "{:?}", spec);
+        }
+        assert_eq!(
+            outcome(&version, "1.80.2"),
+            Err(Some(StableVersionSpec::patch(1, 80, 2)))
+        );
+        assert_eq!(
+            outcome(&version, "1.81"),
+            Err(Some(StableVersionSpec::minor(1, 81)))
+        );
+        assert_eq!(outcome(&version, "1.x"), Err(None));
+        assert_eq!(outcome(&version, "nightly"), Err(None));
+    }
+
+    #[test]
+    fn check_ignores_channel() {
+        let nightly = RustVersion {
+            major: 1,
+            minor: 81,
+            patch: 0,
+            channel: Channel::Nightly {
+                date: Date::new(2024, 6, 1),
+            },
+        };
+        assert_eq!(outcome(&nightly, "1.81"), Ok(()));
+    }
+}
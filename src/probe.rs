@@ -0,0 +1,360 @@
+//! Probes the compiler by compiling small snippets of code, similar to the [`autocfg` crate].
+//!
+//! Version checks are only a proxy for what the compiler actually supports.
+//! Probing answers the question directly, which matters for patched distribution compilers
+//! or alternative implementations.
+//!
+//! The compiler is resolved the same way as [`crate::detect_version`],
+//! honoring `RUSTC` and `RUSTC_WRAPPER`.
+//! Results are cached for the lifetime of the process.
+//!
+//! [`autocfg` crate]: https://github.com/cuviper/autocfg
+//!
+//! # Example
+//! ```no_run
+//! // in build.rs
+//! let prober = rustversion_detect::probe::Prober::new().unwrap();
+//! println!("cargo:rustc-check-cfg=cfg(has_never_type)");
+//! if prober.probe_type("!") {
+//!     println!("cargo:rustc-cfg=has_never_type");
+//! }
+//! ```
+
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::build::{self, Rustc};
+use crate::state;
+
+/// Distinguishes the crate names of probes, so they do not overwrite each other.
+static PROBE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Compiles snippets of code to check whether they are supported by the compiler.
+#[derive(Clone, Debug)]
+pub struct Prober {
+    rustc: Rustc,
+    out_dir: PathBuf,
+    target: Option<String>,
+    rustflags: Vec<String>,
+}
+impl Prober {
+    /// Create a prober configured from the environment of a build script.
+    ///
+    /// Output is written to `OUT_DIR`, compiling for `TARGET` with the flags
+    /// from `CARGO_ENCODED_RUSTFLAGS` (falling back to `RUSTFLAGS`).
+    ///
+    /// # Errors
+    /// Returns an error if `OUT_DIR` is not set,
+    /// which happens when not running in a build script.
+    pub fn new() -> Result<Prober, ProbeError> {
+        match env::var_os("OUT_DIR") {
+            Some(out_dir) => Ok(Prober::with_out_dir(out_dir)),
+            None => Err(ProbeError {
+                kind: ProbeErrorKind::MissingOutDir,
+            }),
+        }
+    }
+
+    /// Create a prober which writes its output to the specified directory.
+    ///
+    /// Other settings are still read from the environment, as in [`Self::new`].
+    pub fn with_out_dir<P: Into<PathBuf>>(out_dir: P) -> Prober {
+        Prober {
+            rustc: Rustc::from_env(),
+            out_dir: out_dir.into(),
+            target: build::target_from_env(),
+            rustflags: build::rustflags_from_env(),
+        }
+    }
+
+    /// The directory where probe output is written.
+    #[inline]
+    #[must_use]
+    pub fn out_dir(&self) -> &Path {
+        &self.out_dir
+    }
+
+    /// Check whether the specified path can be imported with a `use` declaration.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # let prober = rustversion_detect::probe::Prober::new().unwrap();
+    /// let has_hint = prober.probe_path("std::hint::black_box");
+    /// ```
+    #[must_use]
+    pub fn probe_path(&self, path: &str) -> bool {
+        self.probe_raw(&format!("pub use {};", path)).is_ok()
+    }
+
+    /// Check whether the specified type is valid.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # let prober = rustversion_detect::probe::Prober::new().unwrap();
+    /// let has_i128 = prober.probe_type("i128");
+    /// ```
+    #[must_use]
+    pub fn probe_type(&self, ty: &str) -> bool {
+        self.probe_raw(&format!("pub type Probe = {};", ty)).is_ok()
+    }
+
+    /// Check whether the specified expression compiles.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # let prober = rustversion_detect::probe::Prober::new().unwrap();
+    /// let has_abs_diff = prober.probe_expression("1u32.abs_diff(2)");
+    /// ```
+    #[must_use]
+    pub fn probe_expression(&self, expr: &str) -> bool {
+        self.probe_raw(&format!("pub fn probe() {{ let _ = {}; }}", expr))
+            .is_ok()
+    }
+
+    /// Check whether the specified nightly feature gate can be enabled,
+    /// and whether the snippet compiles with it enabled.
+    ///
+    /// The feature is only probed if the [effective channel] allows unstable features
+    /// and `-Zallow-features` (if present) permits it,
+    /// so the presence of `RUSTC_BOOTSTRAP` is respected.
+    /// Nightly features are often renamed or removed,
+    /// so this is more reliable than checking [`RustVersion::is_nightly`].
+    ///
+    /// The snippet may be empty to only check the feature gate itself.
+    ///
+    /// # Errors
+    /// Returns an error if the compiler could not be executed.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use rustversion_detect::probe::{FeatureGateStatus, Prober};
+    /// let prober = Prober::new().unwrap();
+    /// println!("cargo:rustc-check-cfg=cfg(use_portable_simd)");
+    /// let snippet = "pub fn probe() { let _ = std::simd::u32x4::splat(1); }";
+    /// if prober.probe_feature_gate("portable_simd", snippet).unwrap() == FeatureGateStatus::Available {
+    ///     println!("cargo:rustc-cfg=use_portable_simd");
+    /// }
+    /// ```
+    ///
+    /// [effective channel]: crate::RustVersion::effective_channel
+    /// [`RustVersion::is_nightly`]: crate::RustVersion::is_nightly
+    pub fn probe_feature_gate(
+        &self,
+        feature: &str,
+        snippet: &str,
+    ) -> Result<FeatureGateStatus, ProbeError> {
+        if let Ok(version) = crate::detect_version() {
+            if !version.is_feature_allowed(feature) {
+                return Ok(FeatureGateStatus::NotAllowed);
+            }
+        }
+        let gate = format!("#![feature({})]\n", feature);
+        if let Err(e) = self.probe_raw(&gate) {
+            return match e.stderr() {
+                // E0554: `#![feature]` may not be used on the stable release channel
+                Some(stderr) if stderr.contains("E0554") => Ok(FeatureGateStatus::NotAllowed),
+                Some(_) => Ok(FeatureGateStatus::Removed),
+                None => Err(e),
+            };
+        }
+        if snippet.trim().is_empty() {
+            return Ok(FeatureGateStatus::Available);
+        }
+        match self.probe_raw(&format!("{}{}", gate, snippet)) {
+            Ok(()) => Ok(FeatureGateStatus::Available),
+            Err(ref e) if e.is_compile_failure() => Ok(FeatureGateStatus::Removed),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Compile the specified code as the root of a library crate.
+    ///
+    /// The code is compiled with `--emit=metadata`, so no code generation happens.
+    /// Lints are capped at warnings, so `-Dwarnings` does not cause spurious failures.
+    ///
+    /// # Errors
+    /// Returns an error if the code fails to compile,
+    /// or if the compiler could not be executed.
+    pub fn probe_raw(&self, code: &str) -> Result<(), ProbeError> {
+        let key = format!("{:?}", (&self.rustc, &self.target, &self.rustflags, code));
+        if let Some(cached) = state::read(state::probe_cache()).get(&key) {
+            return cached.clone().map_err(ProbeError::compile_failure);
+        }
+        let result = self.compile(code)?;
+        state::write(state::probe_cache()).insert(key, result.clone());
+        result.map_err(ProbeError::compile_failure)
+    }
+
+    /// Run the compiler, returning the error output if compilation fails.
+    fn compile(&self, code: &str) -> Result<Result<(), String>, ProbeError> {
+        let id = PROBE_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let mut command = self.rustc.command();
+        command
+            .arg("--crate-name")
+            .arg(format!("rustversion_detect_probe{}", id))
+            .arg("--crate-type=lib")
+            .arg("--emit=metadata")
+            .arg("--cap-lints=warn")
+            .arg("--out-dir")
+            .arg(&self.out_dir);
+        if let Some(ref target) = self.target {
+            command.arg("--target").arg(target);
+        }
+        command
+            .args(&self.rustflags)
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        let io_error = |cause| ProbeError {
+            kind: ProbeErrorKind::Io(cause),
+        };
+        let mut child = command.spawn().map_err(io_error)?;
+        {
+            let stdin = child.stdin.as_mut().expect("stdin is piped");
+            stdin.write_all(code.as_bytes()).map_err(io_error)?;
+        }
+        let output = child.wait_with_output().map_err(io_error)?;
+        if output.status.success() {
+            Ok(Ok(()))
+        } else {
+            Ok(Err(String::from_utf8_lossy(&output.stderr).into_owned()))
+        }
+    }
+}
+
+/// The result of [`Prober::probe_feature_gate`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FeatureGateStatus {
+    /// The feature gate is accepted and the snippet compiles.
+    Available,
+    /// The feature is unknown to the compiler, or the snippet no longer compiles with it.
+    ///
+    /// This usually means the feature has been renamed, removed, or changed incompatibly.
+    Removed,
+    /// Unstable features are not allowed by this compiler,
+    /// accounting for `RUSTC_BOOTSTRAP` and `-Zallow-features`.
+    NotAllowed,
+}
+impl FeatureGateStatus {
+    /// Check if the feature is available.
+    #[inline]
+    #[must_use]
+    pub fn is_available(&self) -> bool {
+        match *self {
+            FeatureGateStatus::Available => true,
+            _ => false,
+        }
+    }
+}
+
+/// An error that occurs while probing the compiler.
+#[derive(Debug)]
+pub struct ProbeError {
+    kind: ProbeErrorKind,
+}
+#[derive(Debug)]
+enum ProbeErrorKind {
+    MissingOutDir,
+    Io(io::Error),
+    CompileFailure { stderr: String },
+}
+impl ProbeError {
+    fn compile_failure(stderr: String) -> Self {
+        ProbeError {
+            kind: ProbeErrorKind::CompileFailure { stderr },
+        }
+    }
+
+    /// Check if this error indicates the code failed to compile,
+    /// as opposed to a failure to run the compiler.
+    #[inline]
+    #[must_use]
+    pub fn is_compile_failure(&self) -> bool {
+        match self.kind {
+            ProbeErrorKind::CompileFailure { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// The error output of the compiler, if the code failed to compile.
+    #[inline]
+    #[must_use]
+    pub fn stderr(&self) -> Option<&str> {
+        match self.kind {
+            ProbeErrorKind::CompileFailure { ref stderr } => Some(stderr),
+            _ => None,
+        }
+    }
+}
+impl Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ProbeErrorKind::MissingOutDir => f.write_str(
+                "Missing `OUT_DIR` environment variable (not running in a build script?)",
+            ),
+            ProbeErrorKind::Io(ref cause) => write!(f, "Failed to run the compiler: {}", cause),
+            ProbeErrorKind::CompileFailure { ref stderr } => {
+                write!(f, "Failed to compile probe:\n{}", stderr)
+            }
+        }
+    }
+}
+impl Error for ProbeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind {
+            ProbeErrorKind::Io(ref cause) => Some(cause),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FeatureGateStatus, Prober};
+
+    fn prober() -> Prober {
+        let dir = std::env::temp_dir().join("rustversion-detect-probe-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        Prober::with_out_dir(dir)
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // miri cannot spawn processes
+    fn probes() {
+        let prober = prober();
+        assert!(prober.probe_path("std::vec::Vec"));
+        assert!(!prober.probe_path("std::vec::DoesNotExist"));
+        assert!(prober.probe_type("u32"));
+        assert!(!prober.probe_type("DoesNotExist"));
+        assert!(prober.probe_expression("1 + 1"));
+        assert!(!prober.probe_expression("1 + \"a\""));
+        let error = prober.probe_raw("pub fn broken( {}").unwrap_err();
+        assert!(error.is_compile_failure());
+        // cached results are the same
+        assert!(prober.probe_path("std::vec::Vec"));
+        assert!(prober.probe_raw("pub fn broken( {}").is_err());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // miri cannot spawn processes
+    fn feature_gates() {
+        let prober = prober();
+        let expected = if crate::detect_version().unwrap().can_use_unstable_features() {
+            FeatureGateStatus::Removed
+        } else {
+            FeatureGateStatus::NotAllowed
+        };
+        assert_eq!(
+            prober
+                .probe_feature_gate("rustversion_detect_does_not_exist", "")
+                .unwrap(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! Describes how the version was obtained, to help diagnose unexpected detection results.
+//!
+//! See [`detect_version_with_report`].
+
+use core::fmt::{self, Display};
+use std::ffi::OsString;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use crate::{RustVersion, VersionDetectionError};
+
+/// The maximum number of characters of output included by [`DetectionReport`]'s `Display`.
+const EXCERPT_LENGTH: usize = 200;
+
+/// Detect the current version, reporting the commands which were run.
+///
+/// This behaves like [`crate::detect_version`] and shares its cache,
+/// but also returns a [`DetectionReport`] describing how the version was obtained.
+/// The report is returned even if detection fails,
+/// which makes it useful for diagnosing problems in CI.
+///
+/// # Example
+/// ```no_run
+/// // in build.rs
+/// let (result, report) = rustversion_detect::detect_version_with_report();
+/// if result.is_err() {
+///     for line in report.to_string().lines() {
+///         println!("cargo:warning={}", line);
+///     }
+/// }
+/// ```
+pub fn detect_version_with_report() -> (Result<RustVersion, VersionDetectionError>, DetectionReport)
+{
+    if let Some(cached) = crate::cached_compiler_info() {
+        let report = DetectionReport {
+            cached: true,
+            attempts: Vec::new(),
+        };
+        return (Ok(cached.version), report);
+    }
+    let mut attempts = Vec::new();
+    let result = crate::determine_and_cache(&crate::build::Options::default(), &mut attempts);
+    let report = DetectionReport {
+        cached: false,
+        attempts,
+    };
+    (result.map(|info| info.version), report)
+}
+
+/// A description of how the version was obtained.
+///
+/// The `Display` implementation spans multiple lines,
+/// so each line should be printed as a separate `cargo:warning`.
+#[derive(Clone, Debug)]
+pub struct DetectionReport {
+    cached: bool,
+    attempts: Vec<Attempt>,
+}
+impl DetectionReport {
+    /// Check if the version was obtained from the cache of a previous detection.
+    ///
+    /// In this case, there are no [attempts](Self::attempts).
+    #[inline]
+    #[must_use]
+    pub fn is_cached(&self) -> bool {
+        self.cached
+    }
+
+    /// The commands which were run, in order.
+    ///
+    /// There is more than one attempt if the compiler needed to be run again,
+    /// like when `clippy-driver` needs the `--rustc` flag.
+    #[inline]
+    #[must_use]
+    pub fn attempts(&self) -> &[Attempt] {
+        &self.attempts
+    }
+}
+impl Display for DetectionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.cached {
+            return write!(f, "rustc version was obtained from the cache");
+        }
+        if self.attempts.is_empty() {
+            return write!(f, "rustc version was obtained without running any commands");
+        }
+        for (index, attempt) in self.attempts.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "attempt {}: {}", index + 1, attempt)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single execution of the compiler with `--version`.
+#[derive(Clone, Debug)]
+pub struct Attempt {
+    pub(crate) program: OsString,
+    pub(crate) args: Vec<OsString>,
+    pub(crate) env: Vec<(String, String)>,
+    pub(crate) status: Option<ExitStatus>,
+    pub(crate) stdout: String,
+    pub(crate) stderr: String,
+    pub(crate) outcome: AttemptOutcome,
+    pub(crate) duration: Duration,
+}
+impl Attempt {
+    pub(crate) fn new(
+        program: OsString,
+        args: Vec<OsString>,
+        env: Vec<(String, String)>,
+        duration: Duration,
+    ) -> Attempt {
+        Attempt {
+            program,
+            args,
+            env,
+            status: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            outcome: AttemptOutcome::Unrecognized,
+            duration,
+        }
+    }
+
+    /// The program which was executed, which may be a wrapper around the compiler.
+    #[inline]
+    #[must_use]
+    pub fn program(&self) -> &OsString {
+        &self.program
+    }
+
+    /// The arguments passed to the program.
+    #[inline]
+    #[must_use]
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// The environment variables which were set for the command, in addition to the inherited ones.
+    #[inline]
+    #[must_use]
+    pub fn env(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    /// The exit status of the command, or `None` if it could not be executed.
+    #[inline]
+    #[must_use]
+    pub fn status(&self) -> Option<ExitStatus> {
+        self.status
+    }
+
+    /// The standard output of the command, with invalid UTF-8 replaced.
+    #[inline]
+    #[must_use]
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    /// The standard error of the command, with invalid UTF-8 replaced.
+    #[inline]
+    #[must_use]
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+
+    /// The result of parsing the output.
+    #[inline]
+    #[must_use]
+    pub fn outcome(&self) -> &AttemptOutcome {
+        &self.outcome
+    }
+
+    /// How long the command took to run.
+    #[inline]
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+/// Displays the attempt on multiple lines, with excerpts of the output.
+impl Display for Attempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}", self.program.to_string_lossy())?;
+        for arg in &self.args {
+            write!(f, " {}", arg.to_string_lossy())?;
+        }
+        write!(f, "`")?;
+        for (key, value) in &self.env {
+            write!(f, " {}={}", key, value)?;
+        }
+        match self.status {
+            Some(status) => write!(f, " exited with {}", status)?,
+            None => write!(f, " did not run")?,
+        }
+        write!(f, " after {:?}: {}", self.duration, self.outcome)?;
+        if self.status.is_some() {
+            write!(f, "\n  stdout: {:?}", excerpt(&self.stdout))?;
+            write!(f, "\n  stderr: {:?}", excerpt(&self.stderr))?;
+        }
+        Ok(())
+    }
+}
+
+/// Shorten the output to at most [`EXCERPT_LENGTH`] characters.
+fn excerpt(output: &str) -> String {
+    let output = output.trim();
+    match output.char_indices().nth(EXCERPT_LENGTH) {
+        Some((index, _)) => format!("{}...", &output[..index]),
+        None => String::from(output),
+    }
+}
+
+/// The result of parsing the output of an [`Attempt`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AttemptOutcome {
+    /// The version was parsed successfully.
+    Parsed(RustVersion),
+    /// The version was recovered from unexpected output by lenient parsing.
+    ///
+    /// See [`crate::detect::detect_version_lenient`].
+    BestEffort(RustVersion),
+    /// The program is `clippy-driver`, so it was run again with `--rustc`.
+    RetryClippy,
+    /// The program is the `mirai` wrapper, so the compiler was run again without it.
+    RetryMirai,
+    /// The output was not recognized.
+    Unrecognized,
+    /// The output was not valid UTF-8.
+    InvalidUtf8,
+    /// The program could not be executed, with a description of the error.
+    SpawnFailed(String),
+    #[doc(hidden)]
+    __NonExhaustive,
+}
+impl Display for AttemptOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AttemptOutcome::Parsed(ref version) => write!(f, "parsed version {}", version),
+            AttemptOutcome::BestEffort(ref version) => {
+                write!(f, "parsed best-effort version {}", version)
+            }
+            AttemptOutcome::RetryClippy => write!(f, "found clippy-driver, retrying with --rustc"),
+            AttemptOutcome::RetryMirai => write!(f, "found mirai, retrying without the wrapper"),
+            AttemptOutcome::Unrecognized => write!(f, "unrecognized output"),
+            AttemptOutcome::InvalidUtf8 => write!(f, "invalid UTF-8 in output"),
+            AttemptOutcome::SpawnFailed(ref error) => write!(f, "failed to run: {}", error),
+            AttemptOutcome::__NonExhaustive => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{excerpt, Attempt, AttemptOutcome, DetectionReport, EXCERPT_LENGTH};
+    use crate::RustVersion;
+    use std::ffi::OsString;
+    use std::time::Duration;
+
+    #[test]
+    fn display() {
+        let mut attempt = Attempt::new(
+            OsString::from("rustc"),
+            vec![OsString::from("--version")],
+            vec![(String::from("RUSTVERSION"), String::from("1"))],
+            Duration::from_millis(25),
+        );
+        attempt.outcome = AttemptOutcome::SpawnFailed(String::from("not found"));
+        let report = DetectionReport {
+            cached: false,
+            attempts: vec![attempt],
+        };
+        assert_eq!(
+            report.to_string(),
+            "attempt 1: `rustc --version` RUSTVERSION=1 did not run after 25ms: failed to run: not found"
+        );
+        assert_eq!(
+            AttemptOutcome::Parsed(RustVersion::stable(1, 80, 1)).to_string(),
+            "parsed version 1.80.1"
+        );
+    }
+
+    #[test]
+    fn excerpt_length() {
+        assert_eq!(excerpt(" rustc 1.80.1\n"), "rustc 1.80.1");
+        let long = "x".repeat(EXCERPT_LENGTH + 1);
+        assert_eq!(excerpt(&long).len(), EXCERPT_LENGTH + "...".len());
+    }
+}
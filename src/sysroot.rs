@@ -0,0 +1,117 @@
+//! Locates the sysroot of the compiler, and checks which components are installed.
+//!
+//! This allows a missing component to be reported up front,
+//! rather than causing a confusing failure later.
+//!
+//! # Example
+//! ```no_run
+//! // in build.rs
+//! if !rustversion_detect::has_rust_src().unwrap() {
+//!     println!("cargo:warning=Missing rust-src, try `rustup component add rust-src`");
+//! }
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::build::{self, Rustc};
+use crate::{state, VersionDetectionError};
+
+/// Detect the sysroot of the compiler, by executing `rustc --print sysroot`.
+///
+/// The compiler is resolved the same way as [`crate::detect_version`].
+/// Flags from `CARGO_ENCODED_RUSTFLAGS` are honored, since they can override the sysroot.
+///
+/// The result is cached for future calls.
+///
+/// # Errors
+/// Returns an error if unable to execute the compiler.
+pub fn detect_sysroot() -> Result<PathBuf, VersionDetectionError> {
+    if let Some(ref cached) = *state::read(state::sysroot_cache()) {
+        return Ok(cached.clone());
+    }
+    let sysroot = print_path("sysroot", None)?;
+    *state::write(state::sysroot_cache()) = Some(sysroot.clone());
+    Ok(sysroot)
+}
+
+/// Detect the directory containing the standard library for the specified target,
+/// by executing `rustc --print target-libdir --target <triple>`.
+///
+/// The directory is not guaranteed to exist.
+/// See [`has_target_installed`] to check that.
+///
+/// # Errors
+/// Returns an error if unable to execute the compiler,
+/// including if the compiler does not know about the target.
+pub fn detect_target_libdir(triple: &str) -> Result<PathBuf, VersionDetectionError> {
+    print_path("target-libdir", Some(triple))
+}
+
+/// Check if the standard library for the specified target is installed in the sysroot.
+///
+/// With `rustup`, a target is installed using `rustup target add <triple>`.
+/// This inspects `lib/rustlib/<triple>/lib` on disk, looking for the `core` library.
+///
+/// # Errors
+/// Returns an error if unable to [detect the sysroot](detect_sysroot).
+pub fn has_target_installed(triple: &str) -> Result<bool, VersionDetectionError> {
+    let libdir = detect_sysroot()?
+        .join("lib")
+        .join("rustlib")
+        .join(triple)
+        .join("lib");
+    Ok(contains_core_library(&libdir))
+}
+
+/// Check if the source code of the standard library is installed in the sysroot.
+///
+/// With `rustup`, this is installed using `rustup component add rust-src`.
+/// This inspects `lib/rustlib/src/rust` on disk.
+///
+/// # Errors
+/// Returns an error if unable to [detect the sysroot](detect_sysroot).
+pub fn has_rust_src() -> Result<bool, VersionDetectionError> {
+    let src = detect_sysroot()?
+        .join("lib")
+        .join("rustlib")
+        .join("src")
+        .join("rust");
+    // the `library` directory was named `src` before rust 1.47
+    Ok(src.join("library").is_dir() || src.join("src").is_dir())
+}
+
+fn print_path(request: &str, target: Option<&str>) -> Result<PathBuf, VersionDetectionError> {
+    let output = Rustc::from_env().print(request, target, &build::rustflags_from_env())?;
+    let path = output.trim();
+    if path.is_empty() {
+        return Err(VersionDetectionError::new(format!(
+            "Error: empty output from `rustc --print {}`",
+            request
+        )));
+    }
+    Ok(PathBuf::from(path))
+}
+
+fn contains_core_library(libdir: &Path) -> bool {
+    let entries = match fs::read_dir(libdir) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    entries.filter_map(Result::ok).any(|entry| {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        name.starts_with("libcore-") && name.ends_with(".rlib")
+    })
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    #[cfg_attr(miri, ignore)] // miri cannot spawn processes
+    fn sysroot() {
+        let sysroot = super::detect_sysroot().unwrap();
+        assert!(sysroot.is_dir(), "{}", sysroot.display());
+        assert!(!super::has_target_installed("rustversion-detect-unknown-none").unwrap());
+    }
+}
@@ -0,0 +1,322 @@
+//! Parses the output of `rustc --print cfg`, describing the target being compiled for.
+//!
+//! This gives access to target facts like pointer width, atomics support,
+//! panic strategy and enabled target features.
+
+use core::fmt::{self, Display};
+use core::str::FromStr;
+
+use crate::build::{self, Rustc};
+use crate::{state, VersionDetectionError};
+
+/// The names of cfgs which take a value, like `target_os = "linux"`.
+///
+/// Cargo gives both flags and empty values an empty `CARGO_CFG_*` variable,
+/// so these are needed to tell them apart.
+const KEY_VALUE_CFGS: &[&str] = &[
+    "fmt_debug",
+    "panic",
+    "relocation_model",
+    "sanitize",
+    "target_abi",
+    "target_arch",
+    "target_endian",
+    "target_env",
+    "target_family",
+    "target_feature",
+    "target_has_atomic",
+    "target_has_atomic_equal_alignment",
+    "target_has_atomic_load_store",
+    "target_object_format",
+    "target_os",
+    "target_pointer_width",
+    "target_vendor",
+];
+
+/// The configuration options (cfgs) which are set for a target.
+///
+/// There are two kinds of cfgs:
+/// flags like `unix` and key/value pairs like `target_os="linux"`.
+/// A key may have multiple values, like `target_feature`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TargetCfg {
+    entries: Vec<(String, Option<String>)>,
+}
+impl TargetCfg {
+    /// Detect the configuration of the target being compiled for,
+    /// by executing `rustc --print cfg`.
+    ///
+    /// The target is taken from the `TARGET` environment variable (set for build scripts),
+    /// or the host if that is not set.
+    /// Flags from `CARGO_ENCODED_RUSTFLAGS` are honored, since they can enable target features.
+    ///
+    /// The result is cached for each target triple.
+    ///
+    /// # Errors
+    /// Returns an error if unable to execute the compiler or parse its output.
+    pub fn detect() -> Result<TargetCfg, VersionDetectionError> {
+        Self::detect_triple(build::target_from_env().as_ref().map(String::as_str))
+    }
+
+    /// Detect the configuration of the specified target triple.
+    ///
+    /// Behaves like [`Self::detect`], but ignores the `TARGET` environment variable.
+    ///
+    /// # Errors
+    /// Returns an error if unable to execute the compiler or parse its output.
+    pub fn detect_for(triple: &str) -> Result<TargetCfg, VersionDetectionError> {
+        Self::detect_triple(Some(triple))
+    }
+
+    fn detect_triple(triple: Option<&str>) -> Result<TargetCfg, VersionDetectionError> {
+        // the empty string is used for the host
+        let key = triple.unwrap_or("").to_owned();
+        if let Some(cached) = state::read(state::target_cfg_cache()).get(&key) {
+            return Ok(cached.clone());
+        }
+        let output = Rustc::from_env().print("cfg", triple, &build::rustflags_from_env())?;
+        let cfg = output.parse::<TargetCfg>().map_err(|e| {
+            VersionDetectionError::new(format!(
+                "Error: unexpected output from `rustc --print cfg`: {}",
+                e
+            ))
+        })?;
+        state::write(state::target_cfg_cache()).insert(key, cfg.clone());
+        Ok(cfg)
+    }
+
+    /// Collect the cfgs from the `CARGO_CFG_*` environment variables cargo sets for build scripts.
+    ///
+    /// Cargo joins multiple values with commas, and gives flags an empty value.
+    /// An empty value is treated as a flag,
+    /// unless the name is a known key like `target_abi` (see `KEY_VALUE_CFGS`).
+    pub(crate) fn from_cargo_vars<'a, I>(vars: I) -> TargetCfg
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut entries = Vec::new();
+        for (key, value) in vars {
+            if !key.starts_with("CARGO_CFG_") {
+                continue;
+            }
+            let name = key["CARGO_CFG_".len()..].to_lowercase();
+            if value.is_empty() {
+                let is_key = KEY_VALUE_CFGS.contains(&name.as_str());
+                entries.push((name, if is_key { Some(String::new()) } else { None }));
+            } else {
+                for value in value.split(',') {
+                    entries.push((name.clone(), Some(value.to_owned())));
+                }
+            }
+        }
+        entries.sort();
+        TargetCfg { entries }
+    }
+
+    /// Iterate over all cfgs, giving the name and value (if any) of each.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> + '_ {
+        self.entries
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_ref().map(String::as_str)))
+    }
+
+    /// Check if the specified flag is set, like `unix` or `debug_assertions`.
+    #[must_use]
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.iter().any(|entry| entry == (name, None))
+    }
+
+    /// Check if the specified key has the specified value.
+    #[must_use]
+    pub fn has(&self, key: &str, value: &str) -> bool {
+        self.iter().any(|entry| entry == (key, Some(value)))
+    }
+
+    /// Get the first value of the specified key.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.iter()
+            .find_map(|(name, value)| if name == key { value } else { None })
+    }
+
+    /// Iterate over all values of the specified key.
+    pub fn values<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.iter()
+            .filter(move |&(name, _)| name == key)
+            .filter_map(|(_, value)| value)
+    }
+
+    /// Check if the target feature is enabled, like `target_feature = "avx2"`.
+    #[must_use]
+    pub fn target_feature(&self, feature: &str) -> bool {
+        self.has("target_feature", feature)
+    }
+
+    /// Check if the target supports atomic operations of the specified bit width,
+    /// like `target_has_atomic = "64"`.
+    ///
+    /// Older compilers do not set this cfg at all, so this may return `false`
+    /// even if atomics are supported.
+    #[must_use]
+    pub fn has_atomic(&self, bits: u32) -> bool {
+        self.has("target_has_atomic", &bits.to_string())
+    }
+
+    /// Check if the target supports pointer-sized atomics,
+    /// like `target_has_atomic = "ptr"`.
+    #[must_use]
+    pub fn has_atomic_ptr(&self) -> bool {
+        self.has("target_has_atomic", "ptr")
+    }
+
+    /// The width of pointers in bits, like `target_pointer_width = "64"`.
+    #[must_use]
+    pub fn pointer_width(&self) -> Option<u32> {
+        self.get("target_pointer_width")?.parse().ok()
+    }
+
+    /// The panic strategy, like `panic = "unwind"`.
+    ///
+    /// Older compilers do not set this cfg.
+    #[must_use]
+    pub fn panic_strategy(&self) -> Option<&str> {
+        self.get("panic")
+    }
+
+    /// The target architecture, like `target_arch = "x86_64"`.
+    #[must_use]
+    pub fn target_arch(&self) -> Option<&str> {
+        self.get("target_arch")
+    }
+
+    /// The target operating system, like `target_os = "linux"`.
+    #[must_use]
+    pub fn target_os(&self) -> Option<&str> {
+        self.get("target_os")
+    }
+
+    /// The target environment, like `target_env = "gnu"`.
+    #[must_use]
+    pub fn target_env(&self) -> Option<&str> {
+        self.get("target_env")
+    }
+
+    /// Check if the target belongs to the specified family, like `target_family = "unix"`.
+    #[must_use]
+    pub fn is_family(&self, family: &str) -> bool {
+        self.has("target_family", family)
+    }
+}
+/// Parses the output of `rustc --print cfg`, with one cfg per line.
+impl FromStr for TargetCfg {
+    type Err = TargetCfgParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries = Vec::new();
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let entry = match line.find('=') {
+                None => (line.to_owned(), None),
+                Some(index) => {
+                    let value = &line[index + 1..];
+                    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+                        return Err(TargetCfgParseError { line: line.into() });
+                    }
+                    (
+                        line[..index].to_owned(),
+                        Some(value[1..value.len() - 1].to_owned()),
+                    )
+                }
+            };
+            entries.push(entry);
+        }
+        Ok(TargetCfg { entries })
+    }
+}
+
+/// An error that occurs parsing a [`TargetCfg`].
+#[derive(Clone, Debug)]
+pub struct TargetCfgParseError {
+    line: String,
+}
+impl Display for TargetCfgParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid cfg line {:?}", self.line)
+    }
+}
+impl std::error::Error for TargetCfgParseError {}
+
+#[cfg(test)]
+mod test {
+    use super::TargetCfg;
+
+    const LINUX_CFG: &str = r#"debug_assertions
+panic="unwind"
+target_abi=""
+target_arch="x86_64"
+target_endian="little"
+target_env="gnu"
+target_family="unix"
+target_feature="fxsr"
+target_feature="sse"
+target_feature="sse2"
+target_has_atomic="16"
+target_has_atomic="32"
+target_has_atomic="64"
+target_has_atomic="8"
+target_has_atomic="ptr"
+target_os="linux"
+target_pointer_width="64"
+target_vendor="unknown"
+unix
+"#;
+
+    #[test]
+    fn from_cargo_vars() {
+        let cfg = TargetCfg::from_cargo_vars(vec![
+            ("CARGO_CFG_TARGET_OS", "linux"),
+            ("CARGO_CFG_TARGET_ABI", ""),
+            ("CARGO_CFG_TARGET_FEATURE", "fxsr,sse,sse2"),
+            ("CARGO_CFG_TARGET_THREAD_LOCAL", ""),
+            ("CARGO_CFG_UNIX", ""),
+            ("CARGO_PKG_NAME", "example"),
+        ]);
+        assert_eq!(cfg.target_os(), Some("linux"));
+        assert_eq!(cfg.get("target_abi"), Some(""));
+        assert!(!cfg.has_flag("target_abi"));
+        assert!(cfg.has_flag("target_thread_local"));
+        assert_eq!(cfg.get("target_thread_local"), None);
+        assert!(cfg.has_flag("unix"));
+        assert!(cfg.target_feature("sse") && cfg.target_feature("sse2"));
+        assert!(!cfg.has_flag("pkg_name"));
+        assert_eq!(cfg.iter().count(), 7);
+    }
+
+    #[test]
+    fn parse() {
+        let cfg = LINUX_CFG.parse::<TargetCfg>().unwrap();
+        assert!(cfg.has_flag("unix"));
+        assert!(cfg.has_flag("debug_assertions"));
+        assert!(!cfg.has_flag("windows"));
+        assert!(!cfg.has_flag("target_os"));
+        assert_eq!(cfg.target_os(), Some("linux"));
+        assert_eq!(cfg.target_arch(), Some("x86_64"));
+        assert_eq!(cfg.get("target_abi"), Some(""));
+        assert_eq!(cfg.pointer_width(), Some(64));
+        assert_eq!(cfg.panic_strategy(), Some("unwind"));
+        assert!(cfg.has_atomic(64));
+        assert!(!cfg.has_atomic(128));
+        assert!(cfg.has_atomic_ptr());
+        assert!(cfg.target_feature("sse2"));
+        assert!(!cfg.target_feature("avx2"));
+        assert!(cfg.is_family("unix"));
+        assert_eq!(
+            cfg.values("target_feature").collect::<Vec<_>>(),
+            vec!["fxsr", "sse", "sse2"]
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!("target_os=linux".parse::<TargetCfg>().is_err());
+    }
+}
@@ -0,0 +1,503 @@
+//! A minimal parser for the subset of TOML needed to read configuration files.
+//!
+//! This avoids a dependency on a full TOML parser,
+//! which would significantly increase compile times.
+//! Values that are not needed (like numbers and dates) are kept as their raw text.
+
+use std::fmt::{self, Display};
+
+/// A value in a TOML document.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Bool(bool),
+    Array(Vec<Value>),
+    /// An inline table like `{ key = "value" }`.
+    Table(Vec<(Vec<String>, Value)>),
+    /// Any other value (such as a number or date), in its raw form.
+    Other(String),
+}
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Interpret the value as an array of strings.
+    pub fn as_string_array(&self) -> Option<Vec<String>> {
+        match *self {
+            Value::Array(ref values) => values
+                .iter()
+                .map(|value| value.as_str().map(String::from))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed TOML document, as a flat list of fully qualified keys and their values.
+#[derive(Clone, Debug, Default)]
+pub struct Document {
+    entries: Vec<(Vec<String>, Value)>,
+}
+impl Document {
+    /// Get the value of the specified fully qualified key, like `["toolchain", "channel"]`.
+    ///
+    /// Keys specified using inline tables are also found.
+    pub fn get(&self, key: &[&str]) -> Option<&Value> {
+        self.entries
+            .iter()
+            .rev()
+            .find_map(|(path, value)| find_in(path, value, key))
+    }
+}
+
+fn find_in<'a>(path: &[String], value: &'a Value, key: &[&str]) -> Option<&'a Value> {
+    if path.len() > key.len() || !path.iter().zip(key).all(|(a, b)| a == b) {
+        return None;
+    }
+    let rest = &key[path.len()..];
+    if rest.is_empty() {
+        return Some(value);
+    }
+    match *value {
+        Value::Table(ref entries) => entries
+            .iter()
+            .rev()
+            .find_map(|(path, value)| find_in(path, value, rest)),
+        _ => None,
+    }
+}
+
+/// An error parsing a TOML document.
+#[derive(Clone, Debug)]
+pub struct Error {
+    line: usize,
+    message: String,
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid TOML on line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parse a TOML document.
+pub fn parse(text: &str) -> Result<Document, Error> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    let mut table = Vec::new();
+    let mut entries = Vec::new();
+    loop {
+        parser.skip_whitespace_and_newlines();
+        match parser.peek() {
+            None => break,
+            Some('[') => {
+                parser.pos += 1;
+                // arrays of tables are treated like ordinary tables
+                let array = parser.eat('[');
+                parser.skip_whitespace();
+                table = parser.parse_key()?;
+                parser.expect(']')?;
+                if array {
+                    parser.expect(']')?;
+                }
+            }
+            Some(_) => {
+                let mut key = table.clone();
+                key.extend(parser.parse_key()?);
+                parser.expect('=')?;
+                parser.skip_whitespace();
+                let value = parser.parse_value()?;
+                entries.push((key, value));
+            }
+        }
+        parser.expect_line_end()?;
+    }
+    Ok(Document { entries })
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn peek_str(&self, expected: &str) -> bool {
+        expected
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| self.chars.get(self.pos + offset) == Some(&c))
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Error> {
+        if self.eat(expected) {
+            self.skip_whitespace();
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected `{}`", expected)))
+        }
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error {
+            line: self.line,
+            message,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        // `while let` with multiple patterns requires rust 1.33
+        #[allow(clippy::while_let_loop)]
+        loop {
+            match self.peek() {
+                Some(' ') | Some('\t') => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while let Some(c) = self.peek() {
+                if c == '\n' {
+                    break;
+                }
+                self.pos += 1;
+            }
+        }
+    }
+
+    fn skip_whitespace_and_newlines(&mut self) {
+        loop {
+            self.skip_whitespace();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') | Some('\r') => {
+                    self.next();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn expect_line_end(&mut self) -> Result<(), Error> {
+        self.skip_whitespace();
+        self.skip_comment();
+        self.eat('\r');
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.next();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("Unexpected `{}` at end of line", c))),
+        }
+    }
+
+    /// Parse a possibly dotted key, skipping trailing whitespace.
+    fn parse_key(&mut self) -> Result<Vec<String>, Error> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let part = match self.peek() {
+                Some('"') => {
+                    self.next();
+                    self.parse_basic_string()?
+                }
+                Some('\'') => {
+                    self.next();
+                    self.parse_literal_string()?
+                }
+                _ => {
+                    let start = self.pos;
+                    while let Some(c) = self.peek() {
+                        if !(c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                    if start == self.pos {
+                        return Err(self.error("Expected a key".into()));
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            parts.push(part);
+            self.skip_whitespace();
+            if !self.eat('.') {
+                return Ok(parts);
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, Error> {
+        match self.peek() {
+            Some('"') if self.peek_str("\"\"\"") => {
+                self.pos += 3;
+                self.parse_multiline_string("\"\"\"", true)
+                    .map(Value::String)
+            }
+            Some('\'') if self.peek_str("'''") => {
+                self.pos += 3;
+                self.parse_multiline_string("'''", false).map(Value::String)
+            }
+            Some('"') => {
+                self.next();
+                self.parse_basic_string().map(Value::String)
+            }
+            Some('\'') => {
+                self.next();
+                self.parse_literal_string().map(Value::String)
+            }
+            Some('[') => {
+                self.next();
+                let mut values = Vec::new();
+                loop {
+                    self.skip_whitespace_and_newlines();
+                    if self.eat(']') {
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.parse_value()?);
+                    self.skip_whitespace_and_newlines();
+                    if !self.eat(',') {
+                        self.skip_whitespace_and_newlines();
+                        if self.eat(']') {
+                            return Ok(Value::Array(values));
+                        }
+                        return Err(self.error("Expected `,` or `]` in array".into()));
+                    }
+                }
+            }
+            Some('{') => {
+                self.next();
+                let mut entries = Vec::new();
+                self.skip_whitespace();
+                if self.eat('}') {
+                    return Ok(Value::Table(entries));
+                }
+                loop {
+                    let key = self.parse_key()?;
+                    self.expect('=')?;
+                    self.skip_whitespace();
+                    entries.push((key, self.parse_value()?));
+                    self.skip_whitespace();
+                    if self.eat('}') {
+                        return Ok(Value::Table(entries));
+                    }
+                    self.expect(',')?;
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while let Some(c) = self.peek() {
+                    match c {
+                        ',' | ']' | '}' | '#' | '\r' | '\n' => break,
+                        _ => self.pos += 1,
+                    }
+                }
+                let raw: String = self.chars[start..self.pos].iter().collect();
+                let raw = raw.trim_end();
+                match raw {
+                    "" => Err(self.error("Expected a value".into())),
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => Ok(Value::Other(raw.into())),
+                }
+            }
+        }
+    }
+
+    /// Parse the remainder of a basic string, after the opening quote.
+    fn parse_basic_string(&mut self) -> Result<String, Error> {
+        let mut result = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => return Err(self.error("Unterminated string".into())),
+                Some('"') => return Ok(result),
+                Some('\\') => result.push(self.parse_escape()?),
+                Some(c) => result.push(c),
+            }
+        }
+    }
+
+    /// Parse the remainder of a literal string, after the opening quote.
+    fn parse_literal_string(&mut self) -> Result<String, Error> {
+        let mut result = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => return Err(self.error("Unterminated string".into())),
+                Some('\'') => return Ok(result),
+                Some(c) => result.push(c),
+            }
+        }
+    }
+
+    /// Parse the remainder of a multi-line string, after the opening delimiter.
+    fn parse_multiline_string(&mut self, delimiter: &str, escapes: bool) -> Result<String, Error> {
+        let mut result = String::new();
+        // a newline immediately following the delimiter is trimmed
+        self.eat('\r');
+        self.eat('\n');
+        loop {
+            if self.peek_str(delimiter) {
+                self.pos += delimiter.len();
+                return Ok(result);
+            }
+            match self.next() {
+                None => return Err(self.error("Unterminated string".into())),
+                Some('\\') if escapes => match self.peek() {
+                    Some(' ') | Some('\t') | Some('\r') | Some('\n') => {
+                        // a line ending backslash trims all following whitespace
+                        #[allow(clippy::while_let_loop)] // see skip_whitespace
+                        loop {
+                            match self.peek() {
+                                Some(' ') | Some('\t') | Some('\r') | Some('\n') => {
+                                    self.next();
+                                }
+                                _ => break,
+                            }
+                        }
+                    }
+                    _ => result.push(self.parse_escape()?),
+                },
+                Some(c) => result.push(c),
+            }
+        }
+    }
+
+    /// Parse an escape sequence, after the backslash.
+    fn parse_escape(&mut self) -> Result<char, Error> {
+        let c = match self.next() {
+            Some('b') => '\u{8}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{c}',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some(kind @ 'u') | Some(kind @ 'U') => {
+                let len = if kind == 'u' { 4 } else { 8 };
+                let digits: String = self.chars[self.pos..].iter().take(len).collect();
+                self.pos += digits.chars().count();
+                match u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                {
+                    Some(c) if digits.len() == len => c,
+                    _ => return Err(self.error(format!("Invalid unicode escape `{}`", digits))),
+                }
+            }
+            _ => return Err(self.error("Invalid escape sequence".into())),
+        };
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, Value};
+
+    #[test]
+    fn parse_document() {
+        let doc = parse(
+            r#"
+# comment
+top = "level"
+
+[toolchain] # trailing comment
+channel = "nightly-2024-05-01"
+components = [ "rustfmt", 'clippy', ] # trailing comma
+targets = [
+    "wasm32-unknown-unknown",
+    # comment inside array
+    "thumbv7em-none-eabihf"
+]
+escaped = "a\"b\\cé"
+multi = """
+first \
+  second"""
+number = 42
+enabled = true
+
+[build]
+rustc-wrapper = "sccache"
+env.nested = { value = "x", relative = true }
+"#,
+        )
+        .unwrap();
+        let get_str = |key: &[&str]| doc.get(key).and_then(Value::as_str).map(String::from);
+        assert_eq!(get_str(&["top"]), Some("level".into()));
+        assert_eq!(
+            get_str(&["toolchain", "channel"]),
+            Some("nightly-2024-05-01".into())
+        );
+        assert_eq!(
+            doc.get(&["toolchain", "components"])
+                .and_then(Value::as_string_array),
+            Some(vec!["rustfmt".into(), "clippy".into()])
+        );
+        assert_eq!(
+            doc.get(&["toolchain", "targets"])
+                .and_then(Value::as_string_array),
+            Some(vec![
+                "wasm32-unknown-unknown".into(),
+                "thumbv7em-none-eabihf".into()
+            ])
+        );
+        assert_eq!(
+            get_str(&["toolchain", "escaped"]),
+            Some("a\"b\\c\u{e9}".into())
+        );
+        assert_eq!(
+            get_str(&["toolchain", "multi"]),
+            Some("first second".into())
+        );
+        assert_eq!(
+            doc.get(&["toolchain", "number"]),
+            Some(&Value::Other("42".into()))
+        );
+        assert_eq!(doc.get(&["toolchain", "enabled"]), Some(&Value::Bool(true)));
+        assert_eq!(get_str(&["build", "rustc-wrapper"]), Some("sccache".into()));
+        assert_eq!(
+            get_str(&["build", "env", "nested", "value"]),
+            Some("x".into())
+        );
+        assert_eq!(doc.get(&["build", "rustc"]), None);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse("key = \"unterminated").is_err());
+        assert!(parse("key = ").is_err());
+        assert!(parse("[table").is_err());
+        assert!(parse("key = [\"a\" \"b\"]").is_err());
+        assert!(parse("key = \"value\" trailing").is_err());
+    }
+}
@@ -0,0 +1,890 @@
+//! Parses rustup toolchain files, which pin the toolchain used by a project.
+//!
+//! Both the legacy one-line `rust-toolchain` file
+//! and the TOML `rust-toolchain.toml` file are supported.
+//! See the [rustup documentation] for details on the format.
+//!
+//! [rustup documentation]: https://rust-lang.github.io/rustup/overrides.html#the-toolchain-file
+//!
+//! # Example
+//! ```no_run
+//! # use rustversion_detect::toolchain::ToolchainFile;
+//! let current_dir = std::env::current_dir().unwrap();
+//! if let Some((path, file)) = ToolchainFile::find(&current_dir).unwrap() {
+//!     let version = rustversion_detect::detect_version().unwrap();
+//!     match file.channel() {
+//!         Some(channel) if !channel.matches(&version) => {
+//!             println!("{} pins {}, but detected {}", path.display(), channel, version);
+//!         }
+//!         _ => {}
+//!     }
+//! }
+//! ```
+
+use core::fmt::{self, Display};
+use core::str::FromStr;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::build::{self, rustup, Rustc};
+use crate::date::Date;
+use crate::toml;
+use crate::version::{Channel, RustVersion, StableVersionSpec};
+use crate::VersionDetectionError;
+
+/// A rustup toolchain channel, like `stable`, `1.75` or `nightly-2024-05-01`.
+///
+/// The date of a beta or nightly channel follows the rustup convention,
+/// where the date is the day the toolchain was published.
+/// This is usually the day after the commit date shown in `rustc --version`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ToolchainChannel {
+    /// The latest stable release.
+    Stable,
+    /// A beta release, optionally published on a specific date.
+    Beta {
+        /// The date the toolchain was published.
+        date: Option<Date>,
+    },
+    /// A nightly release, optionally published on a specific date.
+    Nightly {
+        /// The date the toolchain was published.
+        date: Option<Date>,
+    },
+    /// A specific stable version, like `1.75` or `1.75.0`.
+    ///
+    /// If the patch version is not specified, rustup uses the latest patch release.
+    Version(StableVersionSpec),
+    /// A custom toolchain name, such as one created with `rustup toolchain link`.
+    Custom(String),
+}
+impl ToolchainChannel {
+    /// Check if the detected version could have come from this channel.
+    ///
+    /// Since the version of a beta compiler does not include a date,
+    /// the date of a beta channel is ignored.
+    /// A custom channel never matches, because its version is unknown.
+    ///
+    /// # Example
+    /// ```
+    /// # use rustversion_detect::toolchain::ToolchainChannel;
+    /// # use rustversion_detect::{Channel, Date, RustVersion};
+    /// let channel: ToolchainChannel = "1.75".parse().unwrap();
+    /// assert!(channel.matches(&RustVersion::stable(1, 75, 1)));
+    ///
+    /// let nightly = RustVersion {
+    ///     major: 1,
+    ///     minor: 80,
+    ///     patch: 0,
+    ///     channel: Channel::Nightly { date: Date::new(2024, 4, 30) },
+    /// };
+    /// let channel: ToolchainChannel = "nightly-2024-05-01".parse().unwrap();
+    /// assert!(channel.matches(&nightly));
+    /// ```
+    #[must_use]
+    pub fn matches(&self, version: &RustVersion) -> bool {
+        match *self {
+            ToolchainChannel::Stable => version.is_stable(),
+            ToolchainChannel::Beta { .. } => version.is_beta(),
+            ToolchainChannel::Nightly { date } => match (version.channel, date) {
+                (Channel::Nightly { .. }, None) => true,
+                (Channel::Nightly { date: commit_date }, Some(date)) => {
+                    commit_date.next_day() == date
+                }
+                _ => false,
+            },
+            ToolchainChannel::Version(spec) => {
+                version.is_stable()
+                    && version.major == spec.major
+                    && version.minor == spec.minor
+                    && spec.patch.map_or(true, |patch| version.patch == patch)
+            }
+            ToolchainChannel::Custom(_) => false,
+        }
+    }
+}
+/// Parses a channel name, like `stable`, `1.75.0` or `beta-2024-05-01`.
+///
+/// Unrecognized names are treated as [custom toolchains](ToolchainChannel::Custom).
+impl FromStr for ToolchainChannel {
+    type Err = ToolchainParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.chars().any(char::is_whitespace) {
+            return Err(ToolchainParseError { text: s.into() });
+        }
+        let (name, date) = match s.find('-') {
+            Some(index) => (&s[..index], Some(&s[index + 1..])),
+            None => (s, None),
+        };
+        let date = match date {
+            Some(date) => match date.parse::<Date>() {
+                Ok(date) => Some(date),
+                Err(_) => return Ok(ToolchainChannel::Custom(s.into())),
+            },
+            None => None,
+        };
+        Ok(match (name, date) {
+            ("stable", None) => ToolchainChannel::Stable,
+            ("beta", date) => ToolchainChannel::Beta { date },
+            ("nightly", date) => ToolchainChannel::Nightly { date },
+            (version, None) => match version.parse::<StableVersionSpec>() {
+                Ok(spec) => ToolchainChannel::Version(spec),
+                Err(_) => ToolchainChannel::Custom(s.into()),
+            },
+            _ => ToolchainChannel::Custom(s.into()),
+        })
+    }
+}
+/// Displays the channel in the format used by rustup.
+impl Display for ToolchainChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, date) = match *self {
+            ToolchainChannel::Stable => ("stable", None),
+            ToolchainChannel::Beta { date } => ("beta", date),
+            ToolchainChannel::Nightly { date } => ("nightly", date),
+            ToolchainChannel::Version(ref spec) => return Display::fmt(spec, f),
+            ToolchainChannel::Custom(ref name) => return f.write_str(name),
+        };
+        f.write_str(name)?;
+        if let Some(date) = date {
+            write!(f, "-{}", date)?;
+        }
+        Ok(())
+    }
+}
+
+/// A rustup toolchain name, like `stable`, `1.75.0` or `nightly-2024-05-01-x86_64-unknown-linux-gnu`.
+///
+/// These names appear in `RUSTUP_TOOLCHAIN`, `+toolchain` arguments,
+/// and the output of `rustup toolchain list`.
+/// A name consists of a [channel](ToolchainChannel), optionally followed by a host triple.
+///
+/// # Example
+/// ```
+/// # use rustversion_detect::toolchain::{ToolchainChannel, ToolchainSpec};
+/// # use rustversion_detect::Date;
+/// let spec: ToolchainSpec = "nightly-2024-05-01-x86_64-unknown-linux-gnu".parse().unwrap();
+/// assert_eq!(
+///     spec.channel,
+///     ToolchainChannel::Nightly {
+///         date: Some(Date::new(2024, 5, 1))
+///     }
+/// );
+/// assert_eq!(spec.host.as_ref().unwrap(), "x86_64-unknown-linux-gnu");
+/// assert_eq!(spec.to_string(), "nightly-2024-05-01-x86_64-unknown-linux-gnu");
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ToolchainSpec {
+    /// The channel of the toolchain.
+    pub channel: ToolchainChannel,
+    /// The host triple, like `x86_64-unknown-linux-gnu`.
+    ///
+    /// If this is `None`, rustup uses the default host.
+    /// This is always `None` for a custom toolchain.
+    pub host: Option<String>,
+}
+impl ToolchainSpec {
+    /// Check if the detected version could have come from this toolchain.
+    ///
+    /// The host triple is ignored, since it is not part of the version.
+    /// See [`ToolchainChannel::matches`] for details.
+    #[inline]
+    #[must_use]
+    pub fn matches(&self, version: &RustVersion) -> bool {
+        self.channel.matches(version)
+    }
+
+    /// Check if this is a custom toolchain, such as one created with `rustup toolchain link`.
+    #[inline]
+    #[must_use]
+    pub fn is_custom(&self) -> bool {
+        match self.channel {
+            ToolchainChannel::Custom(_) => true,
+            _ => false,
+        }
+    }
+}
+impl From<ToolchainChannel> for ToolchainSpec {
+    #[inline]
+    fn from(channel: ToolchainChannel) -> Self {
+        ToolchainSpec {
+            channel,
+            host: None,
+        }
+    }
+}
+/// Parses a toolchain name, in the format `<channel>[-<date>][-<host>]`.
+///
+/// Names that are not in this format are treated as [custom toolchains](ToolchainChannel::Custom).
+impl FromStr for ToolchainSpec {
+    type Err = ToolchainParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let custom = || ToolchainSpec::from(ToolchainChannel::Custom(s.into()));
+        let name_end = s.find('-').unwrap_or(s.len());
+        let mut channel_end = name_end;
+        match &s[..name_end] {
+            "beta" | "nightly" => {
+                // an optional date in the format `-YYYY-MM-DD`
+                let date_end = name_end + 11;
+                if s.len() >= date_end
+                    && s.is_char_boundary(date_end)
+                    && s[name_end + 1..date_end].parse::<Date>().is_ok()
+                {
+                    // the date must be followed by the host (if any)
+                    match s.as_bytes().get(date_end) {
+                        None | Some(&b'-') => channel_end = date_end,
+                        Some(_) => return Ok(custom()),
+                    }
+                }
+            }
+            _ => {}
+        }
+        let channel = s[..channel_end].parse::<ToolchainChannel>()?;
+        if let ToolchainChannel::Custom(_) = channel {
+            return Ok(custom());
+        }
+        let host = if channel_end == s.len() {
+            None
+        } else {
+            let host = &s[channel_end + 1..];
+            // a host triple always has multiple components
+            if !host.contains('-') {
+                return Ok(custom());
+            }
+            Some(host.into())
+        };
+        Ok(ToolchainSpec { channel, host })
+    }
+}
+/// Displays the toolchain name in the format used by rustup.
+impl Display for ToolchainSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.channel, f)?;
+        if let Some(ref host) = self.host {
+            write!(f, "-{}", host)?;
+        }
+        Ok(())
+    }
+}
+
+/// An error that occurs parsing a toolchain name.
+#[derive(Clone, Debug)]
+pub struct ToolchainParseError {
+    text: String,
+}
+impl Display for ToolchainParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid toolchain name {:?}", self.text)
+    }
+}
+impl Error for ToolchainParseError {}
+
+/// An error that occurs converting a [`RustVersion`] into a toolchain name.
+///
+/// See [`RustVersion::to_toolchain_spec`] for details.
+#[derive(Clone, Debug)]
+pub struct ToolchainNameError {
+    pub(crate) version: RustVersion,
+}
+impl ToolchainNameError {
+    /// The version which could not be converted.
+    #[inline]
+    #[must_use]
+    pub fn version(&self) -> &RustVersion {
+        &self.version
+    }
+}
+impl Display for ToolchainNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "No rustup toolchain corresponds to version {}",
+            self.version
+        )
+    }
+}
+impl Error for ToolchainNameError {}
+
+/// The contents of a `rust-toolchain` or `rust-toolchain.toml` file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ToolchainFile {
+    channel: Option<ToolchainSpec>,
+    path: Option<PathBuf>,
+    components: Vec<String>,
+    targets: Vec<String>,
+    profile: Option<String>,
+}
+impl ToolchainFile {
+    /// The names of toolchain files, in the order of precedence used by rustup.
+    pub const FILE_NAMES: &'static [&'static str] = &["rust-toolchain", "rust-toolchain.toml"];
+
+    /// Parse the contents of a toolchain file.
+    ///
+    /// A single line without any TOML syntax is treated as the legacy format,
+    /// which only specifies the channel.
+    ///
+    /// # Errors
+    /// Returns an error if the file is neither valid legacy syntax nor valid TOML,
+    /// or if it specifies both a `channel` and a `path`.
+    pub fn parse(text: &str) -> Result<ToolchainFile, ToolchainFileError> {
+        let trimmed = text.trim();
+        if !trimmed.contains('\n') && !trimmed.contains('[') && !trimmed.contains('=') {
+            let channel = trimmed
+                .parse::<ToolchainSpec>()
+                .map_err(|e| ToolchainFileError::invalid(e.to_string()))?;
+            return Ok(ToolchainFile {
+                channel: Some(channel),
+                ..ToolchainFile::default()
+            });
+        }
+        let doc = toml::parse(text).map_err(|e| ToolchainFileError::invalid(e.to_string()))?;
+        let get_str = |key: &str| -> Result<Option<String>, ToolchainFileError> {
+            match doc.get(&["toolchain", key]) {
+                None => Ok(None),
+                Some(value) => match value.as_str() {
+                    Some(s) => Ok(Some(s.into())),
+                    None => Err(ToolchainFileError::invalid(format!(
+                        "Expected `toolchain.{}` to be a string",
+                        key
+                    ))),
+                },
+            }
+        };
+        let get_array = |key: &str| -> Result<Vec<String>, ToolchainFileError> {
+            match doc.get(&["toolchain", key]) {
+                None => Ok(Vec::new()),
+                Some(value) => value.as_string_array().ok_or_else(|| {
+                    ToolchainFileError::invalid(format!(
+                        "Expected `toolchain.{}` to be an array of strings",
+                        key
+                    ))
+                }),
+            }
+        };
+        let channel = match get_str("channel")? {
+            Some(channel) => Some(
+                channel
+                    .parse::<ToolchainSpec>()
+                    .map_err(|e| ToolchainFileError::invalid(e.to_string()))?,
+            ),
+            None => None,
+        };
+        let path = get_str("path")?.map(PathBuf::from);
+        if channel.is_some() && path.is_some() {
+            return Err(ToolchainFileError::invalid(
+                "Cannot specify both `toolchain.channel` and `toolchain.path`".into(),
+            ));
+        }
+        Ok(ToolchainFile {
+            channel,
+            path,
+            components: get_array("components")?,
+            targets: get_array("targets")?,
+            profile: get_str("profile")?,
+        })
+    }
+
+    /// Read and parse the specified toolchain file.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or [parsed](Self::parse).
+    pub fn read(path: &Path) -> Result<ToolchainFile, ToolchainFileError> {
+        let text = fs::read_to_string(path).map_err(|cause| ToolchainFileError {
+            path: Some(path.into()),
+            kind: ToolchainFileErrorKind::Io(cause),
+        })?;
+        Self::parse(&text).map_err(|e| ToolchainFileError {
+            path: Some(path.into()),
+            ..e
+        })
+    }
+
+    /// Search for a toolchain file, starting in the specified directory
+    /// and walking up through its parents, the way rustup does.
+    ///
+    /// If both `rust-toolchain` and `rust-toolchain.toml` exist in the same directory,
+    /// the legacy `rust-toolchain` file takes precedence (consistent with rustup).
+    ///
+    /// Returns the path of the file along with its contents,
+    /// or `None` if no toolchain file is found.
+    ///
+    /// # Errors
+    /// Returns an error if a toolchain file is found but cannot be read or parsed.
+    pub fn find(dir: &Path) -> Result<Option<(PathBuf, ToolchainFile)>, ToolchainFileError> {
+        for ancestor in dir.ancestors() {
+            for name in Self::FILE_NAMES {
+                let path = ancestor.join(name);
+                if path.is_file() {
+                    let file = Self::read(&path)?;
+                    return Ok(Some((path, file)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// The channel of the toolchain, if specified.
+    ///
+    /// Although discouraged, this may include a host triple.
+    #[inline]
+    #[must_use]
+    pub fn channel(&self) -> Option<&ToolchainSpec> {
+        self.channel.as_ref()
+    }
+
+    /// The path to a custom toolchain, if specified.
+    ///
+    /// A relative path is relative to the directory containing the toolchain file.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_ref().map(PathBuf::as_path)
+    }
+
+    /// The additional components to install, like `rustfmt`.
+    #[inline]
+    #[must_use]
+    pub fn components(&self) -> &[String] {
+        &self.components
+    }
+
+    /// The additional targets to install, like `wasm32-unknown-unknown`.
+    #[inline]
+    #[must_use]
+    pub fn targets(&self) -> &[String] {
+        &self.targets
+    }
+
+    /// The installation profile, like `minimal` or `default`.
+    #[inline]
+    #[must_use]
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_ref().map(String::as_str)
+    }
+}
+
+/// An error that occurs reading a [`ToolchainFile`].
+#[derive(Debug)]
+pub struct ToolchainFileError {
+    path: Option<PathBuf>,
+    kind: ToolchainFileErrorKind,
+}
+#[derive(Debug)]
+enum ToolchainFileErrorKind {
+    Io(io::Error),
+    Invalid(String),
+}
+impl ToolchainFileError {
+    fn invalid(message: String) -> Self {
+        ToolchainFileError {
+            path: None,
+            kind: ToolchainFileErrorKind::Invalid(message),
+        }
+    }
+}
+impl Display for ToolchainFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path {
+            Some(ref path) => write!(f, "Failed to read toolchain file `{}`: ", path.display())?,
+            None => f.write_str("Failed to parse toolchain file: ")?,
+        }
+        match self.kind {
+            ToolchainFileErrorKind::Io(ref cause) => Display::fmt(cause, f),
+            ToolchainFileErrorKind::Invalid(ref message) => f.write_str(message),
+        }
+    }
+}
+impl Error for ToolchainFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind {
+            ToolchainFileErrorKind::Io(ref cause) => Some(cause),
+            ToolchainFileErrorKind::Invalid(_) => None,
+        }
+    }
+}
+
+/// A toolchain installed in rustup's `toolchains` directory.
+///
+/// See [`installed_toolchains`] for details.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstalledToolchain {
+    name: String,
+    spec: ToolchainSpec,
+    path: PathBuf,
+}
+impl InstalledToolchain {
+    /// The name of the toolchain, like `stable-x86_64-unknown-linux-gnu`.
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The toolchain name, parsed into a [`ToolchainSpec`].
+    ///
+    /// A toolchain created with `rustup toolchain link` is a [custom](ToolchainSpec::is_custom) toolchain.
+    #[inline]
+    #[must_use]
+    pub fn spec(&self) -> &ToolchainSpec {
+        &self.spec
+    }
+
+    /// The directory containing the toolchain.
+    ///
+    /// For a linked toolchain, this is a symbolic link to the directory that was linked.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Detect the version of the compiler in this toolchain.
+    ///
+    /// This reads the version from the rustup metadata if possible,
+    /// falling back to executing `bin/rustc --version`.
+    /// Linked toolchains do not have rustup metadata, so always execute the compiler.
+    ///
+    /// The result is not cached.
+    ///
+    /// # Errors
+    /// Returns an error if unable to execute the compiler or parse its output.
+    pub fn version(&self) -> Result<RustVersion, VersionDetectionError> {
+        if let Some(version) = rustup::read_manifest_version(&self.path) {
+            return Ok(version);
+        }
+        let rustc = self
+            .path
+            .join("bin")
+            .join(format!("rustc{}", std::env::consts::EXE_SUFFIX));
+        let compiler = Rustc {
+            rustc: rustc.into_os_string(),
+            wrapper: None,
+            workspace_wrapper: None,
+        };
+        build::query_version(&compiler, None)
+    }
+}
+
+/// List the toolchains installed by rustup, sorted by name.
+///
+/// This scans the `toolchains` directory of `RUSTUP_HOME` (defaulting to `~/.rustup`),
+/// including toolchains created with `rustup toolchain link`.
+/// Versions are only detected when requested with [`InstalledToolchain::version`].
+///
+/// Returns an empty list if rustup is not installed.
+///
+/// # Example
+/// ```no_run
+/// # use rustversion_detect::toolchain::installed_toolchains;
+/// # use rustversion_detect::StableVersionSpec;
+/// let msrv = StableVersionSpec::minor(1, 56);
+/// let oldest = installed_toolchains()
+///     .unwrap()
+///     .into_iter()
+///     .filter_map(|toolchain| toolchain.version().ok())
+///     .filter(|version| version.is_stable() && version.is_since_stable(msrv))
+///     .min_by_key(|version| (version.major, version.minor, version.patch));
+/// ```
+///
+/// # Errors
+/// Returns an error if unable to read the `toolchains` directory.
+pub fn installed_toolchains() -> io::Result<Vec<InstalledToolchain>> {
+    match rustup::rustup_home() {
+        Some(home) => scan_toolchains(&home.join("toolchains")),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn scan_toolchains(dir: &Path) -> io::Result<Vec<InstalledToolchain>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut toolchains = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        // follows symbolic links, which are used by linked toolchains
+        if !path.is_dir() {
+            continue;
+        }
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        let spec = match name.parse::<ToolchainSpec>() {
+            Ok(spec) => spec,
+            Err(_) => continue,
+        };
+        toolchains.push(InstalledToolchain { name, spec, path });
+    }
+    toolchains.sort_by(|first, second| first.name.cmp(&second.name));
+    Ok(toolchains)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_channel() {
+        let cases = &[
+            ("stable", ToolchainChannel::Stable),
+            ("beta", ToolchainChannel::Beta { date: None }),
+            (
+                "beta-2024-05-01",
+                ToolchainChannel::Beta {
+                    date: Some(Date::new(2024, 5, 1)),
+                },
+            ),
+            ("nightly", ToolchainChannel::Nightly { date: None }),
+            (
+                "nightly-2024-05-01",
+                ToolchainChannel::Nightly {
+                    date: Some(Date::new(2024, 5, 1)),
+                },
+            ),
+            (
+                "1.75",
+                ToolchainChannel::Version(StableVersionSpec::minor(1, 75)),
+            ),
+            (
+                "1.75.0",
+                ToolchainChannel::Version(StableVersionSpec::patch(1, 75, 0)),
+            ),
+            (
+                "my-toolchain",
+                ToolchainChannel::Custom("my-toolchain".into()),
+            ),
+            (
+                "stable-2024-05-01",
+                ToolchainChannel::Custom("stable-2024-05-01".into()),
+            ),
+        ];
+        for &(text, ref expected) in cases {
+            let channel = text.parse::<ToolchainChannel>().unwrap();
+            assert_eq!(channel, *expected, "{}", text);
+            assert_eq!(channel.to_string(), text);
+        }
+        assert!("".parse::<ToolchainChannel>().is_err());
+    }
+
+    #[test]
+    fn parse_spec() {
+        let host = || Some(String::from("x86_64-unknown-linux-gnu"));
+        let cases = &[
+            ("stable", ToolchainChannel::Stable, None),
+            (
+                "stable-x86_64-unknown-linux-gnu",
+                ToolchainChannel::Stable,
+                host(),
+            ),
+            (
+                "beta-x86_64-unknown-linux-gnu",
+                ToolchainChannel::Beta { date: None },
+                host(),
+            ),
+            (
+                "nightly-2024-05-01-x86_64-unknown-linux-gnu",
+                ToolchainChannel::Nightly {
+                    date: Some(Date::new(2024, 5, 1)),
+                },
+                host(),
+            ),
+            (
+                "nightly-2024-05-01",
+                ToolchainChannel::Nightly {
+                    date: Some(Date::new(2024, 5, 1)),
+                },
+                None,
+            ),
+            (
+                "1.75.0-x86_64-unknown-linux-gnu",
+                ToolchainChannel::Version(StableVersionSpec::patch(1, 75, 0)),
+                host(),
+            ),
+            (
+                "1.75",
+                ToolchainChannel::Version(StableVersionSpec::minor(1, 75)),
+                None,
+            ),
+            (
+                "my-toolchain",
+                ToolchainChannel::Custom("my-toolchain".into()),
+                None,
+            ),
+            (
+                "stable-custom",
+                ToolchainChannel::Custom("stable-custom".into()),
+                None,
+            ),
+            (
+                "nightly-2024-05-01\u{e9}",
+                ToolchainChannel::Custom("nightly-2024-05-01\u{e9}".into()),
+                None,
+            ),
+            (
+                "nightly-2024-05-01x86_64-unknown-linux-gnu",
+                ToolchainChannel::Custom("nightly-2024-05-01x86_64-unknown-linux-gnu".into()),
+                None,
+            ),
+        ];
+        for &(text, ref channel, ref host) in cases {
+            let spec = text.parse::<ToolchainSpec>().unwrap();
+            assert_eq!(spec.channel, *channel, "{}", text);
+            assert_eq!(spec.host, *host, "{}", text);
+            assert_eq!(spec.to_string(), text);
+        }
+        assert!("".parse::<ToolchainSpec>().is_err());
+        assert!("my-toolchain".parse::<ToolchainSpec>().unwrap().is_custom());
+    }
+
+    #[test]
+    fn parse_legacy_file() {
+        let file = ToolchainFile::parse("nightly-2024-05-01\n").unwrap();
+        assert_eq!(
+            file.channel(),
+            Some(&ToolchainSpec::from(ToolchainChannel::Nightly {
+                date: Some(Date::new(2024, 5, 1))
+            }))
+        );
+        assert!(file.components().is_empty());
+    }
+
+    #[test]
+    fn parse_toml_file() {
+        let file = ToolchainFile::parse(
+            r#"
+[toolchain]
+channel = "1.75.0"
+components = ["rustfmt", "clippy"]
+targets = ["wasm32-unknown-unknown"]
+profile = "minimal"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            file.channel(),
+            Some(&ToolchainSpec::from(ToolchainChannel::Version(
+                StableVersionSpec::patch(1, 75, 0)
+            )))
+        );
+        assert_eq!(file.components(), &["rustfmt", "clippy"]);
+        assert_eq!(file.targets(), &["wasm32-unknown-unknown"]);
+        assert_eq!(file.profile(), Some("minimal"));
+        assert_eq!(file.path(), None);
+
+        let file = ToolchainFile::parse("[toolchain]\npath = \"/opt/rust\"\n").unwrap();
+        assert_eq!(file.path(), Some(Path::new("/opt/rust")));
+        assert_eq!(file.channel(), None);
+
+        assert!(
+            ToolchainFile::parse("[toolchain]\nchannel = \"stable\"\npath = \"/opt/rust\"\n")
+                .is_err()
+        );
+        assert!(ToolchainFile::parse("[toolchain]\ncomponents = \"rustfmt\"\n").is_err());
+    }
+
+    #[test]
+    fn channel_matches() {
+        let nightly = RustVersion {
+            major: 1,
+            minor: 80,
+            patch: 0,
+            channel: Channel::Nightly {
+                date: Date::new(2024, 4, 30),
+            },
+        };
+        let stable = RustVersion::stable(1, 75, 1);
+        let matches = |channel: &str, version: &RustVersion| {
+            channel
+                .parse::<ToolchainChannel>()
+                .unwrap()
+                .matches(version)
+        };
+        assert!(matches("nightly", &nightly));
+        assert!(matches("nightly-2024-05-01", &nightly));
+        assert!(!matches("nightly-2024-04-30", &nightly));
+        assert!(!matches("stable", &nightly));
+        assert!(matches("stable", &stable));
+        assert!(matches("1.75", &stable));
+        assert!(matches("1.75.1", &stable));
+        assert!(!matches("1.75.0", &stable));
+        assert!(!matches("1.76", &stable));
+        assert!(!matches("beta", &stable));
+        assert!(!matches("custom", &stable));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // miri cannot access the filesystem
+    fn scan_toolchains() {
+        let dir = std::env::temp_dir().join(format!(
+            "rustversion-detect-toolchains-{}",
+            std::process::id()
+        ));
+        let nightly = dir.join("nightly-2024-05-01-x86_64-unknown-linux-gnu");
+        fs::create_dir_all(nightly.join("lib").join("rustlib")).unwrap();
+        fs::write(
+            nightly
+                .join("lib")
+                .join("rustlib")
+                .join("multirust-channel-manifest.toml"),
+            "[pkg.rustc]\nversion = \"1.80.0-nightly (ada5e2c7b 2024-04-30)\"\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.join("my-toolchain")).unwrap();
+        fs::write(dir.join("not-a-toolchain.toml"), "").unwrap();
+
+        let toolchains = super::scan_toolchains(&dir).unwrap();
+        let names = toolchains
+            .iter()
+            .map(InstalledToolchain::name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "my-toolchain",
+                "nightly-2024-05-01-x86_64-unknown-linux-gnu"
+            ]
+        );
+        assert!(toolchains[0].spec().is_custom());
+        let version = toolchains[1].version().unwrap();
+        assert_eq!(version.minor, 80);
+        assert!(toolchains[1].spec().matches(&version));
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(super::scan_toolchains(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn version_to_toolchain() {
+        let nightly = RustVersion {
+            major: 1,
+            minor: 80,
+            patch: 0,
+            channel: Channel::Nightly {
+                date: Date::new(2024, 12, 31),
+            },
+        };
+        assert_eq!(nightly.to_toolchain_name().unwrap(), "nightly-2025-01-01");
+        assert!(nightly.to_toolchain_spec().unwrap().matches(&nightly));
+        let stable = RustVersion::stable(1, 78, 0);
+        assert_eq!(stable.to_toolchain_name().unwrap(), "1.78.0");
+        assert!(stable.to_toolchain_spec().unwrap().matches(&stable));
+        let beta = RustVersion {
+            channel: Channel::Beta { number: None },
+            ..stable
+        };
+        assert_eq!(beta.to_toolchain_name().unwrap(), "beta");
+        let dev = RustVersion {
+            channel: Channel::Development,
+            ..stable
+        };
+        assert!(dev.to_toolchain_name().is_err());
+    }
+}
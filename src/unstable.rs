@@ -0,0 +1,188 @@
+//! Determines whether unstable features are usable,
+//! accounting for `RUSTC_BOOTSTRAP` and `-Zallow-features`.
+
+use std::env;
+
+use crate::build;
+use crate::version::{Channel, EffectiveChannel};
+
+/// The parts of the environment which control unstable features.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct UnstableEnv {
+    /// The value of `RUSTC_BOOTSTRAP`.
+    pub rustc_bootstrap: Option<String>,
+    /// The name of the crate being compiled, as seen by rustc.
+    pub crate_name: Option<String>,
+    /// The features listed by the last `-Zallow-features` flag,
+    /// or `None` if there is no such flag.
+    pub allowed_features: Option<Vec<String>>,
+}
+impl UnstableEnv {
+    /// Read the relevant variables from the environment of a build script.
+    ///
+    /// The crate name is derived from `CARGO_PKG_NAME`,
+    /// and the flags are read from `CARGO_ENCODED_RUSTFLAGS` or `RUSTFLAGS`.
+    pub fn from_env() -> Self {
+        UnstableEnv {
+            rustc_bootstrap: env::var("RUSTC_BOOTSTRAP").ok(),
+            // rustc uses underscores in crate names, but package names often use dashes
+            crate_name: env::var("CARGO_PKG_NAME")
+                .ok()
+                .map(|name| name.replace('-', "_")),
+            allowed_features: parse_allowed_features(&build::rustflags_from_env()),
+        }
+    }
+
+    /// Determine the effective channel, mirroring the logic of rustc.
+    pub fn effective_channel(&self, channel: &Channel) -> EffectiveChannel {
+        let nightly = match *channel {
+            Channel::Nightly { .. } | Channel::Development => true,
+            _ => false,
+        };
+        match self.rustc_bootstrap {
+            Some(ref value) if value == "-1" => EffectiveChannel::Stable,
+            Some(ref value) if value == "1" || self.is_bootstrap_crate(value) => {
+                if nightly {
+                    EffectiveChannel::Nightly
+                } else {
+                    EffectiveChannel::Bootstrap
+                }
+            }
+            _ if nightly => EffectiveChannel::Nightly,
+            _ => EffectiveChannel::Stable,
+        }
+    }
+
+    /// Check if the specified feature is permitted by `-Zallow-features`.
+    ///
+    /// This does not check the channel.
+    pub fn is_feature_allowed(&self, feature: &str) -> bool {
+        match self.allowed_features {
+            None => true,
+            Some(ref allowed) => allowed.iter().any(|name| name == feature),
+        }
+    }
+
+    /// Check if any feature is permitted by `-Zallow-features`.
+    pub fn is_any_feature_allowed(&self) -> bool {
+        match self.allowed_features {
+            None => true,
+            Some(ref allowed) => !allowed.is_empty(),
+        }
+    }
+
+    fn is_bootstrap_crate(&self, value: &str) -> bool {
+        match self.crate_name {
+            Some(ref name) => value.split(',').any(|krate| krate == name),
+            None => false,
+        }
+    }
+}
+
+/// Parse the value of the last `-Zallow-features` flag.
+///
+/// Accepts both `-Zallow-features=a,b` and `-Z allow-features=a,b`.
+fn parse_allowed_features(flags: &[String]) -> Option<Vec<String>> {
+    let list = build::unstable_flags(flags)
+        .into_iter()
+        .rev()
+        .find(|option| option.starts_with("allow-features="))
+        .map(|option| &option["allow-features=".len()..])?;
+    Some(
+        list.split(',')
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_allowed_features, UnstableEnv};
+    use crate::build;
+    use crate::version::{Channel, EffectiveChannel};
+    use crate::Date;
+
+    fn bootstrap(value: &str) -> UnstableEnv {
+        UnstableEnv {
+            rustc_bootstrap: Some(value.into()),
+            crate_name: Some("my_crate".into()),
+            allowed_features: None,
+        }
+    }
+
+    #[test]
+    fn effective_channel() {
+        let nightly = Channel::Nightly {
+            date: Date::new(2024, 5, 1),
+        };
+        let cases = &[
+            (
+                UnstableEnv::default(),
+                Channel::Stable,
+                EffectiveChannel::Stable,
+            ),
+            (UnstableEnv::default(), nightly, EffectiveChannel::Nightly),
+            (bootstrap("1"), Channel::Stable, EffectiveChannel::Bootstrap),
+            (
+                bootstrap("1"),
+                Channel::Beta { number: None },
+                EffectiveChannel::Bootstrap,
+            ),
+            (bootstrap("1"), nightly, EffectiveChannel::Nightly),
+            (bootstrap("-1"), nightly, EffectiveChannel::Stable),
+            (
+                bootstrap("-1"),
+                Channel::Development,
+                EffectiveChannel::Stable,
+            ),
+            (bootstrap("0"), Channel::Stable, EffectiveChannel::Stable),
+            (
+                bootstrap("other,my_crate"),
+                Channel::Stable,
+                EffectiveChannel::Bootstrap,
+            ),
+            (
+                bootstrap("other"),
+                Channel::Stable,
+                EffectiveChannel::Stable,
+            ),
+        ];
+        for (env, channel, expected) in cases {
+            assert_eq!(
+                env.effective_channel(channel),
+                *expected,
+                "{:?} & {:?}",
+                env,
+                channel
+            );
+        }
+    }
+
+    #[test]
+    fn allowed_features() {
+        let cases: &[(&[&str], Option<&[&str]>)] = &[
+            (&[], None),
+            (&["-Copt-level=3"], None),
+            (&["-Zallow-features=a,b"], Some(&["a", "b"])),
+            (&["-Z", "allow-features=a"], Some(&["a"])),
+            (&["-Zallow-features="], Some(&[])),
+            (&["-Zallow-features=a", "-Zallow-features=b"], Some(&["b"])),
+        ];
+        for &(flags, expected) in cases {
+            let expected =
+                expected.map(|names| names.iter().map(|&name| String::from(name)).collect());
+            let flags = flags
+                .iter()
+                .map(|&flag| String::from(flag))
+                .collect::<Vec<_>>();
+            assert_eq!(parse_allowed_features(&flags), expected, "{:?}", flags);
+        }
+        // plain `RUSTFLAGS` are used without `CARGO_ENCODED_RUSTFLAGS`
+        let flags = build::parse_rustflags(None, Some("-Z allow-features=a"));
+        assert_eq!(
+            parse_allowed_features(&flags),
+            Some(vec![String::from("a")])
+        );
+    }
+}
@@ -5,6 +5,7 @@ use core::num::ParseIntError;
 use core::str::FromStr;
 
 use crate::date::Date;
+use crate::toolchain::{ToolchainChannel, ToolchainNameError, ToolchainSpec};
 
 /// Specifies a specific stable version, like `1.48`.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -280,8 +281,8 @@ impl RustVersion {
     pub fn is_since_nightly(&self, start: Date) -> bool {
         match self.channel {
             Channel::Nightly { date } => date.is_since(start),
-            Channel::Stable | Channel::Beta => false, // before every nightly
-            Channel::Development => true,             // after every nightly version
+            Channel::Stable | Channel::Beta { .. } => false, // before every nightly
+            Channel::Development => true,                    // after every nightly version
             Channel::__NonExhaustive => unreachable!(),
         }
     }
@@ -299,8 +300,8 @@ impl RustVersion {
     pub fn is_before_nightly(&self, start: Date) -> bool {
         match self.channel {
             Channel::Nightly { date } => date <= start,
-            Channel::Stable | Channel::Beta => false, // before every nightly
-            Channel::Development => true,             // after every nightly version
+            Channel::Stable | Channel::Beta { .. } => false, // before every nightly
+            Channel::Development => true,                    // after every nightly version
             Channel::__NonExhaustive => unreachable!(),
         }
     }
@@ -332,6 +333,147 @@ impl RustVersion {
     pub fn is_development(&self) -> bool {
         self.channel.is_development()
     }
+
+    /// The number of the beta build, like `3` in `1.35.0-beta.3`.
+    ///
+    /// Returns `None` if this is not a beta version,
+    /// or if the compiler did not report a number.
+    #[inline]
+    #[must_use]
+    pub fn beta_number(&self) -> Option<u32> {
+        self.channel.beta_number()
+    }
+
+    /// Check if this version is the specified beta build or any later release.
+    ///
+    /// For the same minor version, a stable release comes after every beta,
+    /// while a nightly release comes before every beta.
+    /// A beta without a [number](Self::beta_number) is conservatively considered
+    /// before every numbered beta.
+    /// Development versions are considered after every beta of the same minor version.
+    ///
+    /// The major version must always be one, or a panic could happen.
+    ///
+    /// ## Example
+    /// ```
+    /// # use rustversion_detect::{Channel, RustVersion};
+    /// let beta = RustVersion {
+    ///     major: 1,
+    ///     minor: 80,
+    ///     patch: 0,
+    ///     channel: Channel::Beta { number: Some(4) },
+    /// };
+    /// assert!(beta.is_since_beta(1, 80, 3));
+    /// assert!(!beta.is_since_beta(1, 80, 5));
+    /// assert!(RustVersion::stable(1, 80, 0).is_since_beta(1, 80, 5));
+    /// ```
+    #[must_use]
+    pub fn is_since_beta(&self, major: u32, minor: u32, number: u32) -> bool {
+        check_major_version(major);
+        if self.major != major || self.minor != minor {
+            return self.is_since_minor_version(major, minor);
+        }
+        match self.channel {
+            Channel::Stable | Channel::Development => true,
+            Channel::Beta { number: actual } => actual.map_or(false, |actual| actual >= number),
+            Channel::Nightly { .. } => false,
+            Channel::__NonExhaustive => unreachable!(),
+        }
+    }
+
+    /// Determine the channel as far as feature gates are concerned.
+    ///
+    /// Unlike [`Self::is_nightly`], this accounts for the `RUSTC_BOOTSTRAP` environment variable.
+    /// Setting `RUSTC_BOOTSTRAP=1` makes a stable compiler accept `#![feature(...)]` like nightly,
+    /// while `RUSTC_BOOTSTRAP=-1` makes a nightly compiler behave like stable.
+    /// The per-crate form `RUSTC_BOOTSTRAP=crate_name` is checked against `CARGO_PKG_NAME`.
+    ///
+    /// This reads the environment, so should only be called from a build script.
+    #[must_use]
+    pub fn effective_channel(&self) -> EffectiveChannel {
+        crate::unstable::UnstableEnv::from_env().effective_channel(&self.channel)
+    }
+
+    /// Check if unstable features can be used with `#![feature(...)]`.
+    ///
+    /// In addition to the [effective channel](Self::effective_channel),
+    /// this checks for `-Zallow-features` in `CARGO_ENCODED_RUSTFLAGS`.
+    /// An empty `-Zallow-features=` forbids all unstable features.
+    ///
+    /// This reads the environment, so should only be called from a build script.
+    #[must_use]
+    pub fn can_use_unstable_features(&self) -> bool {
+        let env = crate::unstable::UnstableEnv::from_env();
+        env.effective_channel(&self.channel)
+            .allows_unstable_features()
+            && env.is_any_feature_allowed()
+    }
+
+    /// Check if the specified unstable feature can be used with `#![feature(...)]`.
+    ///
+    /// Behaves like [`Self::can_use_unstable_features`],
+    /// but also checks that the feature is listed by `-Zallow-features` (if present).
+    /// This does not check whether the compiler actually knows about the feature.
+    ///
+    /// This reads the environment, so should only be called from a build script.
+    #[must_use]
+    pub fn is_feature_allowed(&self, feature: &str) -> bool {
+        let env = crate::unstable::UnstableEnv::from_env();
+        env.effective_channel(&self.channel)
+            .allows_unstable_features()
+            && env.is_feature_allowed(feature)
+    }
+
+    /// The name of the rustup toolchain which provides this version,
+    /// like `1.78.0`, `beta` or `nightly-2024-05-01`.
+    ///
+    /// This can be used to pin exactly the toolchain that produced a build.
+    /// See [`Self::to_toolchain_spec`] for details.
+    ///
+    /// # Errors
+    /// Returns an error if the version is not distributed through rustup.
+    pub fn to_toolchain_name(&self) -> Result<String, ToolchainNameError> {
+        Ok(self.to_toolchain_spec()?.to_string())
+    }
+
+    /// The rustup toolchain which provides this version.
+    ///
+    /// Rustup names a nightly toolchain by the date it was published,
+    /// which is the day after the commit date shown in `rustc --version`.
+    /// Beta toolchains cannot be pinned by version number, so this gives the latest `beta`.
+    ///
+    /// # Example
+    /// ```
+    /// # use rustversion_detect::{Channel, Date, RustVersion};
+    /// let nightly = RustVersion {
+    ///     major: 1,
+    ///     minor: 80,
+    ///     patch: 0,
+    ///     channel: Channel::Nightly {
+    ///         date: Date::new(2024, 4, 30),
+    ///     },
+    /// };
+    /// assert_eq!(nightly.to_toolchain_name().unwrap(), "nightly-2024-05-01");
+    /// assert_eq!(RustVersion::stable(1, 78, 0).to_toolchain_name().unwrap(), "1.78.0");
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error for a development version, which is not distributed through rustup.
+    pub fn to_toolchain_spec(&self) -> Result<ToolchainSpec, ToolchainNameError> {
+        let channel = match self.channel {
+            Channel::Stable => ToolchainChannel::Version(StableVersionSpec::patch(
+                self.major, self.minor, self.patch,
+            )),
+            Channel::Beta { .. } => ToolchainChannel::Beta { date: None },
+            Channel::Nightly { ref date } => ToolchainChannel::Nightly {
+                date: Some(date.next_day()),
+            },
+            Channel::Development | Channel::__NonExhaustive => {
+                return Err(ToolchainNameError { version: *self })
+            }
+        };
+        Ok(ToolchainSpec::from(channel))
+    }
 }
 
 impl From<StableVersionSpec> for RustVersion {
@@ -349,7 +491,10 @@ impl Display for RustVersion {
         write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
         match self.channel {
             Channel::Stable => Ok(()), // nothing
-            Channel::Beta => f.write_str("-beta"),
+            Channel::Beta { number: None } => f.write_str("-beta"),
+            Channel::Beta {
+                number: Some(number),
+            } => write!(f, "-beta.{}", number),
             Channel::Nightly { ref date } => {
                 write!(f, "-nightly ({})", date)
             }
@@ -359,6 +504,48 @@ impl Display for RustVersion {
     }
 }
 
+/// The implementation of the rust compiler.
+///
+/// Alternative compilers are assigned the closest equivalent rustc version,
+/// describing the language level they support.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CompilerKind {
+    /// The official rust compiler, using the default LLVM backend.
+    Rustc,
+    /// The official rust compiler, using the GCC backend from [rustc_codegen_gcc].
+    ///
+    /// [rustc_codegen_gcc]: https://github.com/rust-lang/rustc_codegen_gcc
+    RustcCodegenGcc,
+    /// The official rust compiler, using the Cranelift backend from [rustc_codegen_cranelift].
+    ///
+    /// [rustc_codegen_cranelift]: https://github.com/rust-lang/rustc_codegen_cranelift
+    RustcCodegenCranelift,
+    /// The GCC frontend for rust, [gccrs](https://github.com/Rust-GCC/gccrs).
+    ///
+    /// This targets the language as of rust 1.49.
+    Gccrs,
+    /// An alternative compiler written in C++, [mrustc](https://github.com/thepowersgang/mrustc).
+    ///
+    /// The equivalent rustc version is a best-effort guess,
+    /// based on the version of the standard library it can compile.
+    Mrustc,
+    #[doc(hidden)]
+    __NonExhaustive,
+}
+impl CompilerKind {
+    /// Check if this is the official rust compiler, using any codegen backend.
+    #[inline]
+    #[must_use]
+    pub fn is_rustc(&self) -> bool {
+        match *self {
+            CompilerKind::Rustc
+            | CompilerKind::RustcCodegenGcc
+            | CompilerKind::RustcCodegenCranelift => true,
+            _ => false,
+        }
+    }
+}
+
 /// The [channel] of the rust compiler release.
 ///
 /// [channel]: https://rust-lang.github.io/rustup/concepts/channels.html
@@ -367,7 +554,12 @@ pub enum Channel {
     /// A stable compiler version.
     Stable,
     /// A beta compiler version.
-    Beta,
+    Beta {
+        /// The number of the beta build, like `3` in `1.35.0-beta.3`.
+        ///
+        /// This is `None` if the compiler only reports `beta`.
+        number: Option<u32>,
+    },
     /// A nightly compiler version.
     Nightly {
         /// The date that the compiler was released.
@@ -407,11 +599,21 @@ impl Channel {
     #[must_use]
     pub fn is_beta(&self) -> bool {
         match *self {
-            Channel::Beta => true,
+            Channel::Beta { .. } => true,
             _ => false,
         }
     }
 
+    /// The number of the beta build, or `None` if this is not a numbered beta.
+    #[inline]
+    #[must_use]
+    pub fn beta_number(&self) -> Option<u32> {
+        match *self {
+            Channel::Beta { number } => number,
+            _ => None,
+        }
+    }
+
     /// Check if this is the development channel.
     #[inline]
     #[must_use]
@@ -423,6 +625,33 @@ impl Channel {
     }
 }
 
+/// The channel of the compiler, as far as feature gates are concerned.
+///
+/// Returned by [`RustVersion::effective_channel`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EffectiveChannel {
+    /// Unstable features are rejected.
+    ///
+    /// This includes nightly compilers with `RUSTC_BOOTSTRAP=-1`.
+    Stable,
+    /// Unstable features are accepted, because this is a nightly or development compiler.
+    Nightly,
+    /// Unstable features are accepted, because `RUSTC_BOOTSTRAP` is set
+    /// on a stable or beta compiler.
+    Bootstrap,
+}
+impl EffectiveChannel {
+    /// Check if unstable features are accepted.
+    #[inline]
+    #[must_use]
+    pub fn allows_unstable_features(&self) -> bool {
+        match *self {
+            EffectiveChannel::Stable => false,
+            EffectiveChannel::Nightly | EffectiveChannel::Bootstrap => true,
+        }
+    }
+}
+
 #[inline]
 fn check_major_version(major: u32) {
     assert_eq!(major, 1, "Major version must be 1.*");
@@ -430,7 +659,7 @@ fn check_major_version(major: u32) {
 
 #[cfg(test)]
 mod test {
-    use super::{RustVersion, StableVersionSpec};
+    use super::{Channel, Date, RustVersion, StableVersionSpec};
 
     // (before, after)
     fn versions() -> Vec<(RustVersion, RustVersion)> {
@@ -465,4 +694,30 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_since_beta() {
+        let beta = |number| RustVersion {
+            major: 1,
+            minor: 80,
+            patch: 0,
+            channel: Channel::Beta { number },
+        };
+        assert!(beta(Some(3)).is_since_beta(1, 80, 3));
+        assert!(!beta(Some(2)).is_since_beta(1, 80, 3));
+        assert!(!beta(None).is_since_beta(1, 80, 1));
+        assert!(beta(None).is_since_beta(1, 79, 5));
+        assert!(!beta(Some(9)).is_since_beta(1, 81, 1));
+        assert!(RustVersion::stable(1, 80, 0).is_since_beta(1, 80, 9));
+        let nightly = RustVersion {
+            channel: Channel::Nightly {
+                date: Date::new(2024, 6, 1),
+            },
+            ..beta(None)
+        };
+        assert!(!nightly.is_since_beta(1, 80, 1));
+        assert_eq!(beta(Some(3)).beta_number(), Some(3));
+        assert_eq!(beta(Some(3)).to_string(), "1.80.0-beta.3");
+        assert_eq!(beta(None).to_string(), "1.80.0-beta");
+    }
 }